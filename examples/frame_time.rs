@@ -0,0 +1,219 @@
+//! Renders assets/f22.obj headless and reports the average frame time of the per-triangle
+//! transform path, where every triangle transforms its own copy of its vertices, against
+//! `TransformedMesh`, which transforms each vertex once per frame. Then times the engine
+//! rendering on one thread against all of them, along with what spawning the render
+//! threads costs each frame
+//!
+//! cargo run --release --no-default-features --example frame_time [frames] [threads]
+
+use std::env;
+use std::f64::consts::PI;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use renderer3d::prelude::*;
+use renderer3d::{GlobalLight, LightList, Transform, TransformedMesh};
+use vecx::{Matrix, Vec2, Vec3, VecX};
+
+const WIDTH: usize = 800;
+const HEIGHT: usize = 600;
+
+pub fn main() {
+    let frames = env::args()
+        .nth(1)
+        .map(|frames| frames.parse().expect("frame count"))
+        .unwrap_or(100);
+
+    let mesh = Mesh::load_obj("./assets/f22.obj").unwrap();
+    let camera = camera();
+    let lights = lights();
+
+    let mut buffer = ColorBuffer::new(WIDTH, HEIGHT);
+    let (per_triangle_geometry, per_triangle_frame) =
+        time_frames(frames, &mesh, &mut buffer, |mesh, world, normal| {
+            per_triangle(mesh, world, normal, &camera, &lights)
+        });
+    let (per_vertex_geometry, per_vertex_frame) =
+        time_frames(frames, &mesh, &mut buffer, |mesh, world, normal| {
+            per_vertex(mesh, world, normal, &camera, &lights)
+        });
+
+    println!(
+        "{} faces, {} vertices, {} frames",
+        mesh.faces.len(),
+        mesh.vertices.len(),
+        frames
+    );
+    println!(
+        "per triangle: {:>8.3} ms geometry, {:>8.3} ms frame",
+        millis(per_triangle_geometry),
+        millis(per_triangle_frame)
+    );
+    println!(
+        "per vertex:   {:>8.3} ms geometry, {:>8.3} ms frame",
+        millis(per_vertex_geometry),
+        millis(per_vertex_frame)
+    );
+
+    let threads = env::args()
+        .nth(2)
+        .map(|threads| threads.parse().expect("thread count"))
+        .unwrap_or_else(|| thread::available_parallelism().map_or(1, |threads| threads.get()));
+    let single_thread_frame = time_engine_frames(frames, 1);
+    let tiled_frame = time_engine_frames(frames, threads);
+    let spawning = time_spawning(frames, threads);
+    println!(
+        "engine, 1 thread:   {:>8.3} ms frame",
+        millis(single_thread_frame)
+    );
+    println!(
+        "engine, {} threads: {:>8.3} ms frame, {:>8.3} ms spawning threads",
+        threads,
+        millis(tiled_frame),
+        millis(spawning)
+    );
+}
+
+fn camera() -> Camera {
+    Camera::look_at(
+        Vec3(0.0, 0.0, -5.0),
+        Vec3(0.0, 0.0, 0.0),
+        Vec3(0.0, 1.0, 0.0),
+        CameraProjection::perspective(HEIGHT as f64 / WIDTH as f64, PI / 3.0, 0.1, 100.0),
+    )
+}
+
+fn lights() -> LightList {
+    let mut lights = LightList::new();
+    lights.add(GlobalLight::new(Vec3(0.0, -1.0, 1.0).normalized()));
+    lights
+}
+
+/// Average frame time of the headless engine rendering the turning mesh on `threads`
+/// threads, clearing and presenting included
+fn time_engine_frames(frames: usize, threads: usize) -> Duration {
+    let mut scene = Scene::new();
+    scene.add_mesh("f22", Mesh::load_obj("./assets/f22.obj").unwrap());
+    scene.add_camera("camera", camera());
+    scene.set_active_camera("camera");
+    *scene.lights_mut() = lights();
+
+    let mut render = |eng: &mut EngineCore| {
+        if let Some(mesh) = scene.mesh_mut("f22") {
+            mesh.transform.rotate(Vec3(0.0, 1.0, 0.0), 0.01);
+        }
+        eng.render_scene(&scene);
+    };
+    let mut eng = Engine::headless(EngineConfig::new(EngineConfigParams {
+        width: Some(WIDTH),
+        height: Some(HEIGHT),
+        render_threads: Some(threads),
+        ..EngineConfigParams::default()
+    }));
+
+    let start = Instant::now();
+    eng.run_frames(&mut render, frames);
+    start.elapsed() / frames as u32
+}
+
+/// Average time spent spawning and joining the scoped threads the tiled renderer starts
+/// every frame, measured with threads that have nothing to draw
+fn time_spawning(frames: usize, threads: usize) -> Duration {
+    let start = Instant::now();
+    for _ in 0..frames {
+        thread::scope(|scope| {
+            for _ in 1..threads {
+                scope.spawn(|| {});
+            }
+        });
+    }
+    start.elapsed() / frames as u32
+}
+
+/// Average geometry and whole frame times of `frames` frames of the turning mesh, the
+/// geometry stage returns screen space triangles that are then filled
+fn time_frames<F: FnMut(&Mesh, &Matrix, &Matrix) -> Vec<Triangle>>(
+    frames: usize,
+    mesh: &Mesh,
+    buffer: &mut ColorBuffer,
+    mut geometry: F,
+) -> (Duration, Duration) {
+    let mut transform = Transform::default();
+    let mut geometry_time = Duration::ZERO;
+    let mut frame_time = Duration::ZERO;
+
+    for _ in 0..frames {
+        transform.rotate(Vec3(0.0, 1.0, 0.0), 0.01);
+        let world_matrix = transform.matrix();
+        let normal_matrix = transform.normal_matrix();
+
+        let start = Instant::now();
+        let triangles = geometry(mesh, &world_matrix, &normal_matrix);
+        geometry_time += start.elapsed();
+
+        buffer.clear(0xFF000000);
+        for triangle in triangles {
+            let [a, b, c] = [triangle.a(), triangle.b(), triangle.c()].map(Vec2::from);
+            buffer.draw_filled_triangle(a, b, c, triangle.color());
+        }
+        frame_time += start.elapsed();
+    }
+
+    (geometry_time / frames as u32, frame_time / frames as u32)
+}
+
+/// Each triangle copies and transforms its three vertices
+fn per_triangle(
+    mesh: &Mesh,
+    world_matrix: &Matrix,
+    normal_matrix: &Matrix,
+    camera: &Camera,
+    lights: &LightList,
+) -> Vec<Triangle> {
+    mesh.triangles()
+        .map(|triangle| {
+            triangle
+                .world_transform(world_matrix, normal_matrix)
+                .with_world_positions()
+                .view_transform(camera)
+        })
+        .filter(|triangle| triangle.should_cull(Vec3::ZERO))
+        .flat_map(|triangle| lights.shaded_triangle(&triangle).clipped(camera))
+        .map(to_screen)
+        .collect()
+}
+
+/// Vertices are transformed once and shared by the faces using them
+fn per_vertex(
+    mesh: &Mesh,
+    world_matrix: &Matrix,
+    normal_matrix: &Matrix,
+    camera: &Camera,
+    lights: &LightList,
+) -> Vec<Triangle> {
+    let transformed = TransformedMesh::new(mesh, world_matrix, normal_matrix, camera);
+    (0..transformed.face_count())
+        .filter_map(|face| {
+            let triangle = transformed.triangle(face)?;
+            if !triangle.should_cull(Vec3::ZERO) {
+                return None;
+            }
+            let triangle = lights.shaded_triangle(&triangle);
+            Some(triangle.clipped_from(transformed.clip_points(face)?, camera))
+        })
+        .flatten()
+        .map(to_screen)
+        .collect()
+}
+
+fn to_screen(triangle: Triangle) -> Triangle {
+    let half_width = WIDTH as f64 / 2.0;
+    let half_height = HEIGHT as f64 / 2.0;
+    triangle
+        .scale(Vec3(half_width, -half_height, 1.0))
+        .translate(Vec3(half_width, half_height, 0.0))
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
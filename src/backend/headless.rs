@@ -0,0 +1,35 @@
+use std::path::Path;
+
+use crate::buffer::ColorBuffer;
+use crate::input::InputEvent;
+
+use super::Backend;
+
+/// Backend without any window or input, frames only live in the engine's color buffer
+pub struct HeadlessBackend;
+
+impl HeadlessBackend {
+    pub fn new() -> Self {
+        HeadlessBackend
+    }
+}
+
+impl Default for HeadlessBackend {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Backend for HeadlessBackend {
+    fn poll_events(&mut self) -> Vec<InputEvent> {
+        Vec::new()
+    }
+
+    fn present(&mut self, _color_buffer: &ColorBuffer) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn save_screenshot(&mut self, _file_path: &Path) -> Result<(), String> {
+        Err("Screenshots are not supported by the headless backend".to_string())
+    }
+}
@@ -0,0 +1,24 @@
+mod headless;
+#[cfg(feature = "sdl")]
+mod sdl;
+
+pub use headless::HeadlessBackend;
+#[cfg(feature = "sdl")]
+pub use sdl::SdlBackend;
+
+use std::path::Path;
+
+use crate::buffer::ColorBuffer;
+use crate::input::InputEvent;
+
+/// Where the engine gets its input from and presents its frames to
+pub trait Backend {
+    /// Returns every input event received since the last call
+    fn poll_events(&mut self) -> Vec<InputEvent>;
+
+    /// Displays the content of the color buffer
+    fn present(&mut self, color_buffer: &ColorBuffer) -> Result<(), String>;
+
+    /// Saves what is currently displayed, the image format is chosen from the extension
+    fn save_screenshot(&mut self, file_path: &Path) -> Result<(), String>;
+}
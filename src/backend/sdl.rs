@@ -0,0 +1,224 @@
+use std::path::Path;
+
+use image::RgbImage;
+use sdl2::{
+    event::Event,
+    keyboard::Keycode,
+    mouse::{MouseButton as SdlMouseButton, MouseWheelDirection},
+    pixels::PixelFormatEnum,
+    render::{Canvas, Texture, TextureAccess},
+    video::Window,
+    EventPump,
+};
+
+use crate::buffer::ColorBuffer;
+use crate::engine::EngineConfig;
+use crate::input::{InputEvent, Key, MouseButton};
+
+use super::Backend;
+
+/// Fullscreen borderless SDL2 window
+pub struct SdlBackend {
+    canvas: Canvas<Window>,
+    // Textures are freed along with the canvas' renderer
+    texture: Texture,
+    event_pump: EventPump,
+}
+
+impl SdlBackend {
+    /// Opens the window, the config's dimensions and fps are replaced by the display's
+    pub fn new(config: &mut EngineConfig) -> Self {
+        let ctx = sdl2::init().unwrap();
+        let video = ctx.video().unwrap();
+
+        match video.display_mode(0, 0) {
+            Ok(mode) => {
+                config.set_display_mode(mode.w as usize, mode.h as usize, mode.refresh_rate as u32);
+                println!("Display mode: {:?}", mode);
+            }
+            Err(e) => eprintln!(
+                "Failed to get display mode: {}, using default width and height",
+                e
+            ),
+        };
+
+        let width = config.width();
+        let height = config.height();
+
+        let window = video
+            .window(config.window_title(), width as u32, height as u32)
+            .borderless()
+            .position_centered()
+            .fullscreen()
+            .build()
+            .unwrap();
+
+        let canvas = window.into_canvas().build().unwrap();
+
+        let texture = canvas
+            .texture_creator()
+            .create_texture(
+                PixelFormatEnum::ARGB8888,
+                TextureAccess::Streaming,
+                width as u32,
+                height as u32,
+            )
+            .unwrap();
+
+        let event_pump = ctx.event_pump().unwrap();
+
+        println!("WindowCtx w: {} h: {}", width, height);
+
+        SdlBackend {
+            canvas,
+            texture,
+            event_pump,
+        }
+    }
+
+    fn copy_buffer_to_canvas(&mut self, color_buffer: &ColorBuffer) -> Result<(), String> {
+        self.texture
+            .with_lock(None, |buffer: &mut [u8], _pitch: usize| {
+                buffer.copy_from_slice(color_buffer.pixels());
+            })?;
+
+        self.canvas.copy(&self.texture, None, None)?;
+
+        Ok(())
+    }
+}
+
+impl Backend for SdlBackend {
+    fn poll_events(&mut self) -> Vec<InputEvent> {
+        self.event_pump
+            .poll_iter()
+            .filter_map(|event| match event {
+                Event::Quit { .. } => Some(InputEvent::Quit),
+                Event::KeyDown {
+                    keycode: Some(keycode),
+                    ..
+                } => key_from_keycode(keycode).map(InputEvent::KeyDown),
+                Event::KeyUp {
+                    keycode: Some(keycode),
+                    ..
+                } => key_from_keycode(keycode).map(InputEvent::KeyUp),
+                Event::MouseMotion { xrel, yrel, .. } => Some(InputEvent::MouseMotion {
+                    dx: xrel as f64,
+                    dy: yrel as f64,
+                }),
+                Event::MouseButtonDown { mouse_btn, .. } => {
+                    mouse_button(mouse_btn).map(InputEvent::MouseButtonDown)
+                }
+                Event::MouseButtonUp { mouse_btn, .. } => {
+                    mouse_button(mouse_btn).map(InputEvent::MouseButtonUp)
+                }
+                Event::MouseWheel { y, direction, .. } => {
+                    let amount = match direction {
+                        MouseWheelDirection::Flipped => -y as f64,
+                        _ => y as f64,
+                    };
+                    Some(InputEvent::MouseWheel(amount))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn present(&mut self, color_buffer: &ColorBuffer) -> Result<(), String> {
+        self.copy_buffer_to_canvas(color_buffer)?;
+
+        self.canvas.present();
+
+        Ok(())
+    }
+
+    fn save_screenshot(&mut self, file_path: &Path) -> Result<(), String> {
+        let (width, height) = self.canvas.output_size()?;
+
+        // RGB24 is read back as packed R, G, B bytes whatever the platform's endianness
+        let pixel_data = self.canvas.read_pixels(None, PixelFormatEnum::RGB24)?;
+
+        let img =
+            RgbImage::from_raw(width, height, pixel_data).ok_or("Failed to create image buffer")?;
+
+        img.save(file_path).map_err(|e| e.to_string())?;
+
+        Ok(())
+    }
+}
+
+fn mouse_button(button: SdlMouseButton) -> Option<MouseButton> {
+    match button {
+        SdlMouseButton::Left => Some(MouseButton::Left),
+        SdlMouseButton::Middle => Some(MouseButton::Middle),
+        SdlMouseButton::Right => Some(MouseButton::Right),
+        _ => None,
+    }
+}
+
+fn key_from_keycode(keycode: Keycode) -> Option<Key> {
+    let key = match keycode {
+        Keycode::A => Key::A,
+        Keycode::B => Key::B,
+        Keycode::C => Key::C,
+        Keycode::D => Key::D,
+        Keycode::E => Key::E,
+        Keycode::F => Key::F,
+        Keycode::G => Key::G,
+        Keycode::H => Key::H,
+        Keycode::I => Key::I,
+        Keycode::J => Key::J,
+        Keycode::K => Key::K,
+        Keycode::L => Key::L,
+        Keycode::M => Key::M,
+        Keycode::N => Key::N,
+        Keycode::O => Key::O,
+        Keycode::P => Key::P,
+        Keycode::Q => Key::Q,
+        Keycode::R => Key::R,
+        Keycode::S => Key::S,
+        Keycode::T => Key::T,
+        Keycode::U => Key::U,
+        Keycode::V => Key::V,
+        Keycode::W => Key::W,
+        Keycode::X => Key::X,
+        Keycode::Y => Key::Y,
+        Keycode::Z => Key::Z,
+        Keycode::Num0 | Keycode::Kp0 => Key::Num0,
+        Keycode::Num1 | Keycode::Kp1 => Key::Num1,
+        Keycode::Num2 | Keycode::Kp2 => Key::Num2,
+        Keycode::Num3 | Keycode::Kp3 => Key::Num3,
+        Keycode::Num4 | Keycode::Kp4 => Key::Num4,
+        Keycode::Num5 | Keycode::Kp5 => Key::Num5,
+        Keycode::Num6 | Keycode::Kp6 => Key::Num6,
+        Keycode::Num7 | Keycode::Kp7 => Key::Num7,
+        Keycode::Num8 | Keycode::Kp8 => Key::Num8,
+        Keycode::Num9 | Keycode::Kp9 => Key::Num9,
+        Keycode::Escape => Key::Escape,
+        Keycode::Space => Key::Space,
+        Keycode::Return | Keycode::KpEnter => Key::Return,
+        Keycode::Tab => Key::Tab,
+        Keycode::Left => Key::Left,
+        Keycode::Right => Key::Right,
+        Keycode::Up => Key::Up,
+        Keycode::Down => Key::Down,
+        Keycode::LShift => Key::LShift,
+        Keycode::RShift => Key::RShift,
+        Keycode::LCtrl => Key::LCtrl,
+        Keycode::RCtrl => Key::RCtrl,
+        Keycode::F1 => Key::F1,
+        Keycode::F2 => Key::F2,
+        Keycode::F3 => Key::F3,
+        Keycode::F4 => Key::F4,
+        Keycode::F5 => Key::F5,
+        Keycode::F6 => Key::F6,
+        Keycode::F7 => Key::F7,
+        Keycode::F8 => Key::F8,
+        Keycode::F9 => Key::F9,
+        Keycode::F10 => Key::F10,
+        Keycode::F11 => Key::F11,
+        Keycode::F12 => Key::F12,
+        _ => return None,
+    };
+    Some(key)
+}
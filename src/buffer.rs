@@ -1,3 +1,12 @@
+use std::error::Error;
+use std::ops::Range;
+use std::path::Path;
+
+use image::{DynamicImage, ImageFormat, RgbaImage};
+use vecx::{Vec2, VecX};
+
+use crate::raster::scan_triangle;
+
 pub struct ColorBuffer {
     width: usize,
     height: usize,
@@ -35,6 +44,141 @@ impl ColorBuffer {
     pub fn pixels(&self) -> &[u8] {
         &self.pixels
     }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Copies the buffer into an image, ARGB colors become RGBA pixels
+    pub fn to_image(&self) -> RgbaImage {
+        let pixels = self
+            .pixels
+            .chunks_exact(4)
+            .flat_map(|bgra| [bgra[2], bgra[1], bgra[0], bgra[3]])
+            .collect();
+
+        RgbaImage::from_raw(self.width as u32, self.height as u32, pixels)
+            .expect("color buffer size matches its dimensions")
+    }
+
+    /// Saves the buffer as PNG, JPEG, BMP, TGA or binary PPM depending on the file extension,
+    /// alpha is dropped by the formats without it
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), Box<dyn Error>> {
+        let path = path.as_ref();
+        let image = self.to_image();
+
+        match ImageFormat::from_path(path)? {
+            ImageFormat::Png | ImageFormat::Bmp | ImageFormat::Tga => image.save(path)?,
+            ImageFormat::Jpeg | ImageFormat::Pnm => {
+                DynamicImage::ImageRgba8(image).to_rgb8().save(path)?
+            }
+            format => return Err(format!("Unsupported image format {:?}", format).into()),
+        }
+
+        Ok(())
+    }
+
+    /// Same as `set_pixel` but silently ignores negative and non finite coordinates
+    fn set_pixel_f(&mut self, x: f64, y: f64, color: u32) {
+        if !(x >= 0.0 && y >= 0.0) {
+            return;
+        }
+
+        self.set_pixel(x as usize, y as usize, color);
+    }
+
+    pub(crate) fn pixels_mut(&mut self) -> &mut [u8] {
+        &mut self.pixels
+    }
+}
+
+/// Comparison used by the depth test, the incoming depth is on the left hand side
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DepthFunc {
+    Never,
+    Less,
+    LessEqual,
+    Equal,
+    NotEqual,
+    GreaterEqual,
+    Greater,
+    Always,
+}
+
+impl DepthFunc {
+    pub fn passes(&self, incoming: f64, stored: f64) -> bool {
+        match self {
+            DepthFunc::Never => false,
+            DepthFunc::Less => incoming < stored,
+            DepthFunc::LessEqual => incoming <= stored,
+            DepthFunc::Equal => incoming == stored,
+            DepthFunc::NotEqual => incoming != stored,
+            DepthFunc::GreaterEqual => incoming >= stored,
+            DepthFunc::Greater => incoming > stored,
+            DepthFunc::Always => true,
+        }
+    }
+}
+
+/// Per-pixel depth, stores the interpolated depth of the closest fragment so far (1/w
+/// with perspective projections), nearer fragments have greater values and 0.0 is
+/// infinitely far away
+pub struct DepthBuffer {
+    width: usize,
+    height: usize,
+    depths: Vec<f64>,
+}
+
+impl DepthBuffer {
+    pub const CLEAR_DEPTH: f64 = 0.0;
+
+    pub fn new(width: usize, height: usize) -> DepthBuffer {
+        DepthBuffer {
+            width,
+            height,
+            depths: vec![Self::CLEAR_DEPTH; width * height],
+        }
+    }
+
+    pub fn depth(&self, x: usize, y: usize) -> Option<f64> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+
+        Some(self.depths[(self.width * y) + x])
+    }
+
+    pub fn set_depth(&mut self, x: usize, y: usize, depth: f64) {
+        if x >= self.width || y >= self.height {
+            return;
+        }
+
+        self.depths[(self.width * y) + x] = depth;
+    }
+
+    /// Whether a fragment at `depth` passes `func` against the stored value
+    pub fn test(&self, x: usize, y: usize, depth: f64, func: DepthFunc) -> bool {
+        match self.depth(x, y) {
+            Some(stored) => func.passes(depth, stored),
+            None => false,
+        }
+    }
+
+    pub fn depths(&self) -> &[f64] {
+        &self.depths
+    }
+
+    pub(crate) fn depths_mut(&mut self) -> &mut [f64] {
+        &mut self.depths
+    }
+
+    pub fn clear(&mut self) {
+        self.depths.fill(Self::CLEAR_DEPTH);
+    }
 }
 
 impl ClearColor for ColorBuffer {
@@ -77,26 +221,118 @@ impl Drawable for ColorBuffer {
     }
 
     fn draw_line(&mut self, x0: f64, y0: f64, x1: f64, y1: f64, color: u32) {
-        let dx = (x1 - x0) as isize;
-        let dy = (y1 - y0) as isize;
+        let (width, height) = (self.width, self.height);
+        walk_line(
+            width,
+            height,
+            Vec2(x0, y0),
+            Vec2(x1, y1),
+            0..height,
+            |x, y| self.set_pixel_f(x, y, color),
+        );
+    }
 
-        let run_length = if dx.abs() >= dy.abs() {
-            dx.abs()
-        } else {
-            dy.abs()
-        };
+    fn draw_filled_triangle(&mut self, a: Vec2, b: Vec2, c: Vec2, color: u32) {
+        let (width, height) = (self.width, self.height);
+        scan_triangle(a, b, c, width, 0..height, |x, y, _| {
+            self.set_pixel(x, y, color)
+        });
+    }
+}
+
+/// Calls `plot` with the rounded coordinates of every pixel of the segment once clipped
+/// to a `width` x `height` buffer, coordinates may still be negative or NaN. Only the
+/// steps around `rows` are walked, each step is computed on its own so pixels are the
+/// same whatever the range
+pub(crate) fn walk_line<F: FnMut(f64, f64)>(
+    width: usize,
+    height: usize,
+    from: Vec2,
+    to: Vec2,
+    rows: Range<usize>,
+    mut plot: F,
+) {
+    let (x0, y0, x1, y1) = match clip_line(width, height, from.x(), from.y(), to.x(), to.y()) {
+        Some(line) => line,
+        None => return,
+    };
+
+    let dx = (x1 - x0) as isize;
+    let dy = (y1 - y0) as isize;
+
+    let run_length = if dx.abs() >= dy.abs() {
+        dx.abs()
+    } else {
+        dy.abs()
+    };
+
+    let inc_x = dx as f64 / run_length as f64;
+    let inc_y = dy as f64 / run_length as f64;
+
+    if run_length == 0 {
+        plot(x0.round(), y0.round());
+        return;
+    }
+
+    // Steps whose y rounds into `rows`, widened by one on each side for rounding
+    let steps = if inc_y == 0.0 {
+        if !rows.contains(&(y0.round() as usize)) {
+            return;
+        }
+        0..=run_length
+    } else {
+        let t0 = (rows.start as f64 - 0.5 - y0) / inc_y;
+        let t1 = (rows.end as f64 - 0.5 - y0) / inc_y;
+        let first = (t0.min(t1).floor() as isize - 1).max(0);
+        let last = (t0.max(t1).ceil() as isize + 1).min(run_length);
+        first..=last
+    };
+
+    for step in steps {
+        let step = step as f64;
+        plot((x0 + inc_x * step).round(), (y0 + inc_y * step).round());
+    }
+}
+
+/// Clips the segment to the buffer's bounds (Liang-Barsky), returns `None` if it lies outside
+fn clip_line(
+    width: usize,
+    height: usize,
+    x0: f64,
+    y0: f64,
+    x1: f64,
+    y1: f64,
+) -> Option<(f64, f64, f64, f64)> {
+    let max_x = width as f64 - 1.0;
+    let max_y = height as f64 - 1.0;
+
+    let dx = x1 - x0;
+    let dy = y1 - y0;
+
+    let mut t0: f64 = 0.0;
+    let mut t1: f64 = 1.0;
+
+    for (p, q) in [(-dx, x0), (dx, max_x - x0), (-dy, y0), (dy, max_y - y0)] {
+        if p == 0.0 {
+            if q < 0.0 {
+                return None;
+            }
+            continue;
+        }
 
-        let inc_x = dx as f64 / run_length as f64;
-        let inc_y = dy as f64 / run_length as f64;
+        let t = q / p;
+        if p < 0.0 {
+            t0 = t0.max(t);
+        } else {
+            t1 = t1.min(t);
+        }
 
-        let mut cx = x0 as f64;
-        let mut cy = y0 as f64;
-        for _ in 0..=run_length {
-            self.set_pixel(cx.round() as usize, cy.round() as usize, color);
-            cx += inc_x;
-            cy += inc_y;
+        if t0 > t1 {
+            return None;
         }
     }
+
+    Some((x0 + t0 * dx, y0 + t0 * dy, x0 + t1 * dx, y0 + t1 * dy))
 }
 
 pub trait Drawable {
@@ -104,6 +340,14 @@ pub trait Drawable {
     fn draw_rect(&mut self, x: usize, y: usize, width: usize, height: usize, color: u32);
     fn draw_point(&mut self, x: usize, y: usize, color: u32);
     fn draw_line(&mut self, x0: f64, y0: f64, x1: f64, y1: f64, color: u32);
+    fn draw_filled_triangle(&mut self, a: Vec2, b: Vec2, c: Vec2, color: u32);
+
+    /// Draws the outline of a triangle
+    fn draw_triangle(&mut self, a: Vec2, b: Vec2, c: Vec2, color: u32) {
+        self.draw_line(a.x(), a.y(), b.x(), b.y(), color);
+        self.draw_line(b.x(), b.y(), c.x(), c.y(), color);
+        self.draw_line(c.x(), c.y(), a.x(), a.y(), color);
+    }
 }
 
 pub trait ClearColor {
@@ -113,3 +357,122 @@ pub trait ClearColor {
 pub trait ClearAuto {
     fn clear(&mut self);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn images_hold_rgba_pixels() {
+        let mut colors = ColorBuffer::new(3, 2);
+        colors.set_pixel(2, 1, 0x80FF4020);
+
+        let image = colors.to_image();
+        assert_eq!(image.dimensions(), (3, 2));
+        assert_eq!(image.get_pixel(2, 1).0, [0xFF, 0x40, 0x20, 0x80]);
+        assert_eq!(image.get_pixel(0, 0).0, [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn saved_pngs_round_trip() {
+        let directory = std::env::temp_dir().join(format!("buffer-save-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("frame.png");
+
+        let mut colors = ColorBuffer::new(3, 2);
+        colors.set_pixel(1, 0, 0xC0102030);
+        colors.save(&path).unwrap();
+        let image = image::open(&path).unwrap().to_rgba8();
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!(image.dimensions(), (3, 2));
+        assert_eq!(image.get_pixel(1, 0).0, [0x10, 0x20, 0x30, 0xC0]);
+    }
+
+    #[test]
+    fn unknown_extensions_are_errors() {
+        let colors = ColorBuffer::new(3, 2);
+        let path = std::env::temp_dir().join(format!("buffer-save-{}.xyz", std::process::id()));
+        assert!(colors.save(&path).is_err());
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn lines_are_clipped_to_the_buffer() {
+        // Inside lines are kept as is
+        assert_eq!(
+            clip_line(10, 8, 1.0, 2.0, 7.0, 5.0),
+            Some((1.0, 2.0, 7.0, 5.0))
+        );
+        // Cut where they leave the buffer, the last pixel is at width - 1
+        assert_eq!(
+            clip_line(10, 8, -4.0, 3.0, 14.0, 3.0),
+            Some((0.0, 3.0, 9.0, 3.0))
+        );
+        assert_eq!(
+            clip_line(10, 8, 2.0, -2.0, 6.0, 10.0),
+            Some((2.0 + 4.0 / 12.0 * 2.0, 0.0, 2.0 + 4.0 / 12.0 * 9.0, 7.0))
+        );
+        // Entirely on one side, or passing by a corner
+        assert_eq!(clip_line(10, 8, -3.0, 1.0, -1.0, 6.0), None);
+        assert_eq!(clip_line(10, 8, 2.0, 9.0, 8.0, 12.0), None);
+        assert_eq!(clip_line(10, 8, -3.0, 2.0, 2.0, -3.0), None);
+        // Points are clipped like lines
+        assert_eq!(
+            clip_line(10, 8, 4.0, 4.0, 4.0, 4.0),
+            Some((4.0, 4.0, 4.0, 4.0))
+        );
+        assert_eq!(clip_line(10, 8, 12.0, 4.0, 12.0, 4.0), None);
+    }
+
+    #[test]
+    fn depth_funcs_compare_the_incoming_depth_to_the_stored_one() {
+        // Whether farther, equal and nearer incoming depths pass against 0.5
+        let expected = [
+            (DepthFunc::Never, [false, false, false]),
+            (DepthFunc::Less, [true, false, false]),
+            (DepthFunc::LessEqual, [true, true, false]),
+            (DepthFunc::Equal, [false, true, false]),
+            (DepthFunc::NotEqual, [true, false, true]),
+            (DepthFunc::GreaterEqual, [false, true, true]),
+            (DepthFunc::Greater, [false, false, true]),
+            (DepthFunc::Always, [true, true, true]),
+        ];
+
+        for (func, passes) in expected {
+            let results = [0.25, 0.5, 0.75].map(|incoming| func.passes(incoming, 0.5));
+            assert_eq!(results, passes, "{:?}", func);
+        }
+    }
+
+    #[test]
+    fn cleared_depths_are_behind_everything() {
+        let mut depths = DepthBuffer::new(4, 3);
+        assert!(depths
+            .depths()
+            .iter()
+            .all(|&d| d == DepthBuffer::CLEAR_DEPTH));
+
+        depths.set_depth(1, 2, 0.5);
+        assert_eq!(depths.depth(1, 2), Some(0.5));
+        assert!(depths.test(1, 2, 0.75, DepthFunc::Greater));
+        assert!(!depths.test(1, 2, 0.25, DepthFunc::Greater));
+
+        depths.clear();
+        assert!(depths.depths().iter().all(|&d| d == 0.0));
+        // Greater is nearer, anything in front of the far plane passes
+        assert!(depths.test(1, 2, 1e-9, DepthFunc::Greater));
+        assert!(!depths.test(1, 2, 0.0, DepthFunc::Greater));
+    }
+
+    #[test]
+    fn depths_outside_the_buffer_are_ignored() {
+        let mut depths = DepthBuffer::new(4, 3);
+        depths.set_depth(4, 0, 1.0);
+        depths.set_depth(0, 3, 1.0);
+
+        assert!(depths.depths().iter().all(|&d| d == 0.0));
+        assert_eq!(depths.depth(4, 0), None);
+        assert!(!depths.test(0, 3, 1.0, DepthFunc::Always));
+    }
+}
@@ -0,0 +1,245 @@
+use vecx::{Vec4, VecX};
+
+/// A vertex that can be clipped in homogeneous clip space
+pub(crate) trait ClipVertex: Copy {
+    fn position(&self) -> Vec4;
+
+    /// Vertex at `t` along the segment from `self` to `other`
+    fn lerp(&self, other: &Self, t: f64) -> Self;
+}
+
+impl ClipVertex for Vec4 {
+    fn position(&self) -> Vec4 {
+        *self
+    }
+
+    fn lerp(&self, other: &Self, t: f64) -> Self {
+        Vec4(
+            self.x() + (other.x() - self.x()) * t,
+            self.y() + (other.y() - self.y()) * t,
+            self.z() + (other.z() - self.z()) * t,
+            self.w() + (other.w() - self.w()) * t,
+        )
+    }
+}
+
+/// Planes of the view frustum in clip space, the projection maps depth to `0 <= z <= w`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ClipPlane {
+    Near,
+    Far,
+    Left,
+    Right,
+    Bottom,
+    Top,
+}
+
+impl ClipPlane {
+    pub const ALL: [ClipPlane; 6] = [
+        ClipPlane::Near,
+        ClipPlane::Far,
+        ClipPlane::Left,
+        ClipPlane::Right,
+        ClipPlane::Bottom,
+        ClipPlane::Top,
+    ];
+
+    /// Signed distance of the point to the plane, positive on the inside
+    fn distance(&self, point: &Vec4) -> f64 {
+        match self {
+            ClipPlane::Near => point.z(),
+            ClipPlane::Far => point.w() - point.z(),
+            ClipPlane::Left => point.w() + point.x(),
+            ClipPlane::Right => point.w() - point.x(),
+            ClipPlane::Bottom => point.w() + point.y(),
+            ClipPlane::Top => point.w() - point.y(),
+        }
+    }
+
+    /// Sutherland-Hodgman clipping of a convex polygon against this plane
+    fn clip<V: ClipVertex>(&self, polygon: &[V]) -> Vec<V> {
+        let mut clipped = Vec::with_capacity(polygon.len() + 1);
+
+        for (i, current) in polygon.iter().enumerate() {
+            let next = &polygon[(i + 1) % polygon.len()];
+
+            let current_distance = self.distance(&current.position());
+            let next_distance = self.distance(&next.position());
+
+            if current_distance >= 0.0 {
+                clipped.push(*current);
+            }
+
+            if (current_distance >= 0.0) != (next_distance >= 0.0) {
+                let t = current_distance / (current_distance - next_distance);
+                clipped.push(current.lerp(next, t));
+            }
+        }
+
+        clipped
+    }
+}
+
+/// Clips a convex polygon against every frustum plane, an empty result means it was
+/// entirely outside
+pub(crate) fn clip_polygon<V: ClipVertex>(polygon: &[V]) -> Vec<V> {
+    let mut clipped = polygon.to_vec();
+
+    for plane in ClipPlane::ALL {
+        if clipped.len() < 3 {
+            return Vec::new();
+        }
+        clipped = plane.clip(&clipped);
+    }
+
+    if clipped.len() < 3 {
+        return Vec::new();
+    }
+
+    clipped
+}
+
+/// Clips a segment against every frustum plane, `None` when it is entirely outside
+pub(crate) fn clip_segment<V: ClipVertex>(from: V, to: V) -> Option<(V, V)> {
+    let (mut t0, mut t1) = (0.0, 1.0);
+
+    for plane in ClipPlane::ALL {
+        let from_distance = plane.distance(&from.position());
+        let to_distance = plane.distance(&to.position());
+
+        if from_distance < 0.0 && to_distance < 0.0 {
+            return None;
+        }
+
+        let t = from_distance / (from_distance - to_distance);
+        if from_distance < 0.0 {
+            t0 = f64::max(t0, t);
+        } else if to_distance < 0.0 {
+            t1 = f64::min(t1, t);
+        }
+    }
+
+    if t0 > t1 {
+        return None;
+    }
+
+    Some((from.lerp(&to, t0), from.lerp(&to, t1)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Clip space point carrying a single attribute
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    struct Tagged(Vec4, f64);
+
+    impl ClipVertex for Tagged {
+        fn position(&self) -> Vec4 {
+            self.0
+        }
+
+        fn lerp(&self, other: &Self, t: f64) -> Self {
+            Tagged(self.0.lerp(&other.0, t), self.1 + (other.1 - self.1) * t)
+        }
+    }
+
+    fn inside(point: &Vec4) -> bool {
+        ClipPlane::ALL
+            .iter()
+            .all(|plane| plane.distance(point) >= -1e-12)
+    }
+
+    #[test]
+    fn triangles_inside_the_frustum_are_kept_as_is() {
+        let triangle = [
+            Vec4(-0.5, -0.5, 0.5, 1.0),
+            Vec4(0.5, -0.5, 0.5, 1.0),
+            Vec4(0.0, 0.5, 0.5, 1.0),
+        ];
+        assert_eq!(clip_polygon(&triangle), triangle);
+    }
+
+    #[test]
+    fn triangles_straddling_the_near_plane_become_quads() {
+        // The first point is behind the near plane
+        let triangle = [
+            Tagged(Vec4(0.0, 0.0, -1.0, 1.0), 0.0),
+            Tagged(Vec4(0.5, 0.0, 1.0, 2.0), 1.0),
+            Tagged(Vec4(0.0, 0.5, 0.5, 1.0), 2.0),
+        ];
+        let clipped = clip_polygon(&triangle);
+
+        assert_eq!(clipped.len(), 4);
+        assert!(clipped.iter().all(|vertex| inside(&vertex.0)));
+        // The points in front are kept along with one intersection per crossing edge
+        assert!(clipped.contains(&triangle[1]) && clipped.contains(&triangle[2]));
+        let intersections: Vec<Tagged> = clipped
+            .into_iter()
+            .filter(|vertex| !triangle.contains(vertex))
+            .collect();
+        assert_eq!(intersections.len(), 2);
+
+        // Attributes are interpolated at the same `t` as the positions, half way along the
+        // first edge and a third along the last one
+        for Tagged(position, attribute) in intersections {
+            assert!(position.z().abs() < 1e-12);
+            let expected = if position.x() > 0.0 {
+                Tagged(Vec4(0.25, 0.0, 0.0, 1.5), 0.5)
+            } else {
+                Tagged(Vec4(0.0, 1.0 / 3.0, 0.0, 1.0), 4.0 / 3.0)
+            };
+            let components = |v: Vec4| [v.x(), v.y(), v.z(), v.w()];
+            let close = components(position)
+                .iter()
+                .zip(components(expected.0))
+                .all(|(a, b)| (a - b).abs() < 1e-12);
+            assert!(close, "{:?}", position);
+            assert!((attribute - expected.1).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn triangles_outside_the_frustum_are_dropped() {
+        let behind = [
+            Vec4(0.0, 0.0, -1.0, 1.0),
+            Vec4(0.5, 0.0, -0.5, 1.0),
+            Vec4(0.0, 0.5, -2.0, 1.0),
+        ];
+        let left = [
+            Vec4(-3.0, 0.0, 0.5, 1.0),
+            Vec4(-2.0, 0.5, 0.5, 1.0),
+            Vec4(-4.0, -0.5, 0.5, 1.0),
+        ];
+        // Each point is inside some planes but no point is inside all of them
+        let around_a_corner = [
+            Vec4(-3.0, 0.5, 0.5, 1.0),
+            Vec4(0.5, -3.0, 0.5, 1.0),
+            Vec4(-3.0, -3.0, 0.5, 1.0),
+        ];
+
+        for triangle in [behind, left, around_a_corner] {
+            assert!(clip_polygon(&triangle).is_empty(), "{:?}", triangle);
+        }
+    }
+
+    #[test]
+    fn segments_are_cut_at_the_planes_they_cross() {
+        let from = Tagged(Vec4(-3.0, 0.0, 0.5, 1.0), 0.0);
+        let to = Tagged(Vec4(3.0, 0.0, 0.5, 1.0), 6.0);
+        let (start, end) = clip_segment(from, to).unwrap();
+        assert_eq!(start, Tagged(Vec4(-1.0, 0.0, 0.5, 1.0), 2.0));
+        assert_eq!(end, Tagged(Vec4(1.0, 0.0, 0.5, 1.0), 4.0));
+
+        let inside = Tagged(Vec4(0.0, 0.0, 0.5, 1.0), 3.0);
+        assert_eq!(clip_segment(inside, from).unwrap(), (inside, start));
+
+        let behind = Tagged(Vec4(0.0, 0.0, -1.0, 1.0), 0.0);
+        let also_behind = Tagged(Vec4(0.5, 0.5, -0.5, 1.0), 0.0);
+        assert!(clip_segment(behind, also_behind).is_none());
+        // Outside different planes at each end and never inside
+        let left = Tagged(Vec4(-3.0, 0.5, 0.5, 1.0), 0.0);
+        let below = Tagged(Vec4(0.5, -3.0, 0.5, 1.0), 0.0);
+        assert!(clip_segment(left, below).is_none());
+    }
+}
@@ -0,0 +1,133 @@
+use std::f64::consts::FRAC_PI_2;
+
+use vecx::{Vec3, VecX};
+
+use crate::input::{InputState, Key, MouseButton};
+use crate::projection::Camera;
+
+/// Pitch stays just short of straight up or down, where yaw would flip around
+const MAX_PITCH: f64 = FRAC_PI_2 - 0.01;
+
+/// Moves a camera from the engine's input, see `EngineCore::control_camera`
+pub trait CameraController {
+    fn update(&mut self, camera: &mut Camera, input: &InputState, delta_time: f64);
+}
+
+/// WASD moves along the view direction, E and Q up and down, dragging with the right
+/// mouse button looks around
+pub struct FlyController {
+    /// Units per second
+    pub speed: f64,
+    /// Radians per pixel of mouse motion
+    pub mouse_sensitivity: f64,
+}
+
+impl FlyController {
+    pub fn new(speed: f64) -> Self {
+        FlyController {
+            speed,
+            mouse_sensitivity: 0.003,
+        }
+    }
+}
+
+impl CameraController for FlyController {
+    fn update(&mut self, camera: &mut Camera, input: &InputState, delta_time: f64) {
+        if input.is_button_down(MouseButton::Right) {
+            let (dx, dy) = input.mouse_motion();
+            let pitch = (camera.pitch() + dy * self.mouse_sensitivity).clamp(-MAX_PITCH, MAX_PITCH);
+            camera.set_orientation(
+                camera.yaw() + dx * self.mouse_sensitivity,
+                pitch,
+                camera.roll(),
+            );
+        }
+
+        let axis = |positive: Key, negative: Key| {
+            input.is_key_down(positive) as i32 as f64 - input.is_key_down(negative) as i32 as f64
+        };
+        let forward = axis(Key::W, Key::S);
+        let right = axis(Key::D, Key::A);
+        let up = axis(Key::E, Key::Q);
+
+        let (f, r, u) = (camera.forward(), camera.right(), camera.up());
+        let step = self.speed * delta_time;
+        let movement = Vec3(
+            (f.x() * forward + r.x() * right + u.x() * up) * step,
+            (f.y() * forward + r.y() * right + u.y() * up) * step,
+            (f.z() * forward + r.z() * right + u.z() * up) * step,
+        );
+
+        camera.set_position(*camera.position() + movement);
+    }
+}
+
+/// Circles around a target, dragging with the left mouse button turns around it and the
+/// wheel zooms in and out
+pub struct OrbitController {
+    target: Vec3,
+    distance: f64,
+    yaw: f64,
+    pitch: f64,
+    /// Radians per pixel of mouse motion
+    pub rotate_sensitivity: f64,
+    /// Fraction of the distance covered per wheel step
+    pub zoom_step: f64,
+    pub min_distance: f64,
+    pub max_distance: f64,
+}
+
+impl OrbitController {
+    pub fn new(target: Vec3, distance: f64) -> Self {
+        OrbitController {
+            target,
+            distance,
+            yaw: 0.0,
+            pitch: 0.0,
+            rotate_sensitivity: 0.005,
+            zoom_step: 0.1,
+            min_distance: 0.1,
+            max_distance: 1000.0,
+        }
+    }
+
+    pub fn target(&self) -> Vec3 {
+        self.target
+    }
+
+    pub fn set_target(&mut self, target: Vec3) {
+        self.target = target;
+    }
+
+    pub fn distance(&self) -> f64 {
+        self.distance
+    }
+
+    /// Turns around the target to `yaw` and `pitch`, like `Camera::set_orientation`
+    pub fn set_angles(&mut self, yaw: f64, pitch: f64) {
+        self.yaw = yaw;
+        self.pitch = pitch.clamp(-MAX_PITCH, MAX_PITCH);
+    }
+}
+
+impl CameraController for OrbitController {
+    /// Mouse motion is independent of the frame time, `delta_time` goes unused
+    fn update(&mut self, camera: &mut Camera, input: &InputState, _delta_time: f64) {
+        if input.is_button_down(MouseButton::Left) {
+            let (dx, dy) = input.mouse_motion();
+            self.yaw += dx * self.rotate_sensitivity;
+            self.pitch = (self.pitch + dy * self.rotate_sensitivity).clamp(-MAX_PITCH, MAX_PITCH);
+        }
+
+        let zoom = (1.0 - self.zoom_step).powf(input.wheel());
+        self.distance = (self.distance * zoom).clamp(self.min_distance, self.max_distance);
+
+        camera.set_orientation(self.yaw, self.pitch, 0.0);
+        let forward = camera.forward();
+        camera.set_position(Vec3(
+            self.target.x() - forward.x() * self.distance,
+            self.target.y() - forward.y() * self.distance,
+            self.target.z() - forward.z() * self.distance,
+        ));
+    }
+}
@@ -1,18 +1,29 @@
-use image::{ImageBuffer, Rgba};
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::thread;
 use std::time::{Duration, Instant};
 
-use sdl2::{
-    event::Event,
-    keyboard::Keycode,
-    pixels::PixelFormatEnum,
-    render::{Canvas, Texture, UpdateTextureError},
-    video::Window,
-    EventPump,
-};
-
-use crate::buffer::{ClearAuto, ClearColor, ColorBuffer, Drawable};
+use vecx::{Matrix, Vec2, Vec3, Vec4, VecX};
+
+#[cfg(feature = "sdl")]
+use crate::backend::SdlBackend;
+use crate::backend::{Backend, HeadlessBackend};
+use crate::buffer::{ClearAuto, ClearColor, ColorBuffer, DepthBuffer, DepthFunc, Drawable};
+use crate::controller::CameraController;
+use crate::input::{InputEvent, InputState, Key};
+use crate::lighting::LightList;
+use crate::material::Material;
+use crate::mesh::{Mesh, TransformedMesh, Triangle};
+use crate::projection::Camera;
+use crate::scene::Scene;
+use crate::texture::Texture;
+use crate::tiles::{self, DrawCommand, RasterSettings, Shading};
 use crate::utils::NumOption;
 
+const WIREFRAME_COLOR: u32 = 0xFF00FF00;
+const VERTEX_COLOR: u32 = 0xFFFF0000;
+
 pub struct EngineConfigParams {
     pub window_title: Option<String>,
     pub width: Option<usize>,
@@ -21,6 +32,14 @@ pub struct EngineConfigParams {
     pub fps: Option<u32>,
     pub render_mode: Option<RenderMode>,
     pub backface_culling_enabled: Option<bool>,
+    pub depth_func: Option<DepthFunc>,
+    pub depth_write_enabled: Option<bool>,
+    pub rasterizer: Option<Rasterizer>,
+    pub render_threads: Option<usize>,
+    pub screenshot_dir: Option<PathBuf>,
+    pub screenshot_format: Option<ScreenshotFormat>,
+    pub screenshot_key: Option<Key>,
+    pub culling_key: Option<Key>,
 }
 
 impl Default for EngineConfigParams {
@@ -33,6 +52,14 @@ impl Default for EngineConfigParams {
             fps: None,
             render_mode: None,
             backface_culling_enabled: None,
+            depth_func: None,
+            depth_write_enabled: None,
+            rasterizer: None,
+            render_threads: None,
+            screenshot_dir: None,
+            screenshot_format: None,
+            screenshot_key: None,
+            culling_key: None,
         }
     }
 }
@@ -49,6 +76,14 @@ impl Default for EngineConfig {
             fps: 60,
             render_mode: RenderMode::Solid,
             backface_culling_enabled: true,
+            depth_func: DepthFunc::Greater,
+            depth_write_enabled: true,
+            rasterizer: Rasterizer::Scanline,
+            render_threads: 1,
+            screenshot_dir: PathBuf::from("screenshots"),
+            screenshot_format: ScreenshotFormat::Png,
+            screenshot_key: Key::F12,
+            culling_key: Key::C,
         }
     }
 }
@@ -61,6 +96,14 @@ pub struct EngineConfig {
     fps: u32,
     render_mode: RenderMode,
     backface_culling_enabled: bool,
+    depth_func: DepthFunc,
+    depth_write_enabled: bool,
+    rasterizer: Rasterizer,
+    render_threads: usize,
+    screenshot_dir: PathBuf,
+    screenshot_format: ScreenshotFormat,
+    screenshot_key: Key,
+    culling_key: Key,
 }
 
 impl EngineConfig {
@@ -80,6 +123,18 @@ impl EngineConfig {
             backface_culling_enabled: params
                 .backface_culling_enabled
                 .unwrap_or(default.backface_culling_enabled),
+            depth_func: params.depth_func.unwrap_or(default.depth_func),
+            depth_write_enabled: params
+                .depth_write_enabled
+                .unwrap_or(default.depth_write_enabled),
+            rasterizer: params.rasterizer.unwrap_or(default.rasterizer),
+            render_threads: params.render_threads.unwrap_or(default.render_threads),
+            screenshot_dir: params.screenshot_dir.unwrap_or(default.screenshot_dir),
+            screenshot_format: params
+                .screenshot_format
+                .unwrap_or(default.screenshot_format),
+            screenshot_key: params.screenshot_key.unwrap_or(default.screenshot_key),
+            culling_key: params.culling_key.unwrap_or(default.culling_key),
         }
     }
 
@@ -115,6 +170,43 @@ impl EngineConfig {
         self.backface_culling_enabled
     }
 
+    pub fn depth_func(&self) -> DepthFunc {
+        self.depth_func
+    }
+
+    pub fn depth_write_enabled(&self) -> bool {
+        self.depth_write_enabled
+    }
+
+    pub fn rasterizer(&self) -> Rasterizer {
+        self.rasterizer
+    }
+
+    /// Threads `EngineCore::render_scene` rasterizes with, 0 uses every available core
+    pub fn render_threads(&self) -> usize {
+        self.render_threads
+    }
+
+    /// Directory screenshots are saved in, created when taking the first one
+    pub fn screenshot_dir(&self) -> &Path {
+        &self.screenshot_dir
+    }
+
+    pub fn screenshot_format(&self) -> ScreenshotFormat {
+        self.screenshot_format
+    }
+
+    /// Key taking a screenshot when released
+    pub fn screenshot_key(&self) -> Key {
+        self.screenshot_key
+    }
+
+    /// Key toggling back-face culling when released, keep it clear of the camera
+    /// controllers' keys
+    pub fn culling_key(&self) -> Key {
+        self.culling_key
+    }
+
     pub fn set_render_mode(&mut self, mode: RenderMode) {
         self.render_mode = mode;
     }
@@ -122,6 +214,45 @@ impl EngineConfig {
     pub fn set_backface_culling_enabled(&mut self, enabled: bool) {
         self.backface_culling_enabled = enabled;
     }
+
+    pub fn set_depth_func(&mut self, func: DepthFunc) {
+        self.depth_func = func;
+    }
+
+    pub fn set_depth_write_enabled(&mut self, enabled: bool) {
+        self.depth_write_enabled = enabled;
+    }
+
+    pub fn set_rasterizer(&mut self, rasterizer: Rasterizer) {
+        self.rasterizer = rasterizer;
+    }
+
+    pub fn set_render_threads(&mut self, threads: usize) {
+        self.render_threads = threads;
+    }
+
+    pub fn set_screenshot_dir<P: Into<PathBuf>>(&mut self, dir: P) {
+        self.screenshot_dir = dir.into();
+    }
+
+    pub fn set_screenshot_format(&mut self, format: ScreenshotFormat) {
+        self.screenshot_format = format;
+    }
+
+    pub fn set_screenshot_key(&mut self, key: Key) {
+        self.screenshot_key = key;
+    }
+
+    pub fn set_culling_key(&mut self, key: Key) {
+        self.culling_key = key;
+    }
+
+    #[cfg(feature = "sdl")]
+    pub(crate) fn set_display_mode(&mut self, width: usize, height: usize, fps: u32) {
+        self.width = width;
+        self.height = height;
+        self.fps = fps;
+    }
 }
 
 pub enum RenderMode {
@@ -129,6 +260,44 @@ pub enum RenderMode {
     Wireframe,
     Solid,
     SolidWireframe,
+    Textured,
+    /// Lighting computed at each point and interpolated across the triangle
+    Gouraud,
+    /// Blinn-Phong lighting computed at every pixel from interpolated normals
+    Phong,
+}
+
+/// Algorithm used to fill depth tested triangles
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rasterizer {
+    /// Fills rows between the triangle's edges
+    Scanline,
+    /// Tests pixel centers against edge functions with a top-left fill rule, adjacent
+    /// triangles neither leave gaps nor overlap
+    EdgeFunction,
+}
+
+/// Image format screenshots are saved as
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScreenshotFormat {
+    Png,
+    Jpeg,
+    Bmp,
+    Tga,
+    /// Binary PPM
+    Ppm,
+}
+
+impl ScreenshotFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ScreenshotFormat::Png => "png",
+            ScreenshotFormat::Jpeg => "jpg",
+            ScreenshotFormat::Bmp => "bmp",
+            ScreenshotFormat::Tga => "tga",
+            ScreenshotFormat::Ppm => "ppm",
+        }
+    }
 }
 
 type EngineUpdateFn<'a> = &'a mut dyn FnMut(&mut EngineCore);
@@ -141,50 +310,33 @@ pub struct Engine<'a> {
 }
 
 impl<'a> Engine<'a> {
+    /// Opens a fullscreen window sized to the primary display
+    #[cfg(feature = "sdl")]
     pub fn build(mut config: EngineConfig) -> Engine<'a> {
-        let ctx = sdl2::init().unwrap();
-        let video = ctx.video().unwrap();
-
-        match video.display_mode(0, 0) {
-            Ok(mode) => {
-                config.width = mode.w as usize;
-                config.height = mode.h as usize;
-                config.fps = mode.refresh_rate as u32;
-                println!("Display mode: {:?}", mode);
-            }
-            Err(e) => eprintln!(
-                "Failed to get display mode: {}, using default width and height",
-                e
-            ),
-        };
-
-        let width = config.width;
-        let height = config.height;
-
-        let window = video
-            .window(&config.window_title, width as u32, height as u32)
-            .borderless()
-            .position_centered()
-            .fullscreen()
-            .build()
-            .unwrap();
-
-        let canvas = window.into_canvas().build().unwrap();
-
-        let color_buffer = ColorBuffer::new(width, height);
-
-        let event_pump = ctx.event_pump().unwrap();
+        let backend = SdlBackend::new(&mut config);
+        Self::with_backend(config, Box::new(backend))
+    }
 
-        println!("WindowCtx w: {} h: {}", width, height);
+    /// Renders into the color buffer only, without opening a window or reading input
+    pub fn headless(config: EngineConfig) -> Engine<'a> {
+        Self::with_backend(config, Box::new(HeadlessBackend::new()))
+    }
 
+    pub fn with_backend(config: EngineConfig, backend: Box<dyn Backend>) -> Engine<'a> {
+        let color_buffer = ColorBuffer::new(config.width, config.height);
+        let depth_buffer = DepthBuffer::new(config.width, config.height);
         let fps = config.fps;
 
         Engine {
             core: EngineCore {
                 config,
-                canvas,
                 color_buffer,
-                event_pump,
+                depth_buffer,
+                backend,
+                running: true,
+                input: InputState::new(),
+                delta_time: 1.0 / fps as f64,
+                screenshot_count: 0,
             },
             update: None,
             previous_frame_time: Instant::now(),
@@ -201,6 +353,24 @@ impl<'a> Engine<'a> {
         self.update();
     }
 
+    /// Runs `f` for at most `frames` frames without any frame pacing, or until it calls
+    /// [`EngineCore::stop`], and returns the last rendered frame
+    pub fn run_frames(&mut self, f: EngineUpdateFn<'a>, frames: usize) -> &ColorBuffer {
+        self.update = Some(f);
+        self.core.running = true;
+
+        // Frames are as far apart as the target frame rate asks, whatever time they took
+        self.core.delta_time = 1.0 / self.core.config.fps as f64;
+        for _ in 0..frames {
+            self.frame();
+            if !self.core.running {
+                break;
+            }
+        }
+
+        &self.core.color_buffer
+    }
+
     pub fn user_update(&mut self) {
         self.update.as_mut().unwrap()(&mut self.core);
     }
@@ -208,30 +378,14 @@ impl<'a> Engine<'a> {
     pub fn update(&mut self) {
         self.target_frame_time = Duration::new(0, 1_000_000_000u32 / self.core.config.fps);
 
-        let texture_creator = self.core.canvas.texture_creator();
-        let mut texture = texture_creator
-            .create_texture(
-                PixelFormatEnum::ARGB8888,
-                sdl2::render::TextureAccess::Streaming,
-                self.core.config.width as u32,
-                self.core.config.height as u32,
-            )
-            .unwrap();
-
-        let mut running = true;
-        while running {
-            self.previous_frame_time = Instant::now();
-
-            running = self.core.process_input();
+        self.core.running = true;
+        self.previous_frame_time = Instant::now() - self.target_frame_time;
+        while self.core.running {
+            let frame_start = Instant::now();
+            self.core.delta_time = (frame_start - self.previous_frame_time).as_secs_f64();
+            self.previous_frame_time = frame_start;
 
-            self.user_update();
-
-            match self.core.render_buffer(&mut texture) {
-                Ok(()) => (),
-                Err(e) => {
-                    eprintln!("Failed to update texture: {}", e);
-                }
-            }
+            self.frame();
 
             let now = Instant::now();
             let frame_time = now - self.previous_frame_time;
@@ -240,151 +394,383 @@ impl<'a> Engine<'a> {
             if frame_time.as_nanos() < self.target_frame_time.as_nanos() {
                 ::std::thread::sleep(self.target_frame_time - frame_time);
             }
+        }
+    }
+
+    fn frame(&mut self) {
+        self.core.clear();
+
+        self.core.process_input();
+
+        self.user_update();
 
-            self.core.clear();
+        match self.core.render_buffer() {
+            Ok(()) => (),
+            Err(e) => {
+                eprintln!("Failed to present frame: {}", e);
+            }
         }
     }
 }
 
 pub struct EngineCore {
     config: EngineConfig,
-    canvas: Canvas<Window>,
     color_buffer: ColorBuffer,
-    event_pump: EventPump,
+    depth_buffer: DepthBuffer,
+    backend: Box<dyn Backend>,
+    running: bool,
+    input: InputState,
+    delta_time: f64,
+    screenshot_count: usize,
 }
 
 impl EngineCore {
-    fn process_input(&mut self) -> bool {
-        for event in self.event_pump.poll_iter() {
+    fn process_input(&mut self) {
+        self.input.begin_frame();
+        for event in self.backend.poll_events() {
+            self.input.handle(&event);
             match event {
                 // QUIT
-                Event::Quit { .. }
-                | Event::KeyDown {
-                    keycode: Some(Keycode::Escape),
-                    ..
-                } => {
+                InputEvent::Quit | InputEvent::KeyDown(Key::Escape) => {
                     println!("Received quit event, shutting down");
-                    return false;
+                    self.stop();
                 }
                 // KEYBOARD EVENTS
-                Event::KeyUp {
-                    keycode: Some(Keycode::S),
-                    ..
-                } => {
-                    let file_path: &str = "screenshot.jpg";
-                    println!("Saving screenshot");
-                    match self.save_screenshot(file_path) {
-                        Ok(()) => (),
-                        Err(_e) => eprint!("Failed to write screenshot"),
+                InputEvent::KeyUp(key) if key == self.config.screenshot_key => {
+                    match self.save_screenshot() {
+                        Ok(path) => println!("Saved screenshot to {}", path.display()),
+                        Err(e) => eprintln!("Failed to write screenshot: {}", e),
                     }
-                    return true;
                 }
-                Event::KeyUp {
-                    keycode: Some(Keycode::Num1),
-                    ..
-                } => {
+                InputEvent::KeyUp(Key::Num1) => {
                     println!("Wireframe and vertices");
                     self.config.set_render_mode(RenderMode::VerticesWireframe);
-                    return true;
                 }
-                Event::KeyUp {
-                    keycode: Some(Keycode::Num2),
-                    ..
-                } => {
+                InputEvent::KeyUp(Key::Num2) => {
                     println!("Wireframe");
                     self.config.set_render_mode(RenderMode::Wireframe);
-                    return true;
                 }
-                Event::KeyUp {
-                    keycode: Some(Keycode::Num3),
-                    ..
-                } => {
+                InputEvent::KeyUp(Key::Num3) => {
                     println!("Solid");
                     self.config.set_render_mode(RenderMode::Solid);
-                    return true;
                 }
-                Event::KeyUp {
-                    keycode: Some(Keycode::Num4),
-                    ..
-                } => {
+                InputEvent::KeyUp(Key::Num4) => {
                     println!("Solid Wireframe");
                     self.config.set_render_mode(RenderMode::SolidWireframe);
-                    return true;
                 }
-                Event::KeyUp {
-                    keycode: Some(Keycode::C),
-                    ..
-                } => {
-                    println!("Enable back-face culling");
-                    self.config.set_backface_culling_enabled(true);
-                    return true;
+                InputEvent::KeyUp(Key::Num5) => {
+                    println!("Textured");
+                    self.config.set_render_mode(RenderMode::Textured);
+                }
+                InputEvent::KeyUp(Key::Num6) => {
+                    println!("Gouraud");
+                    self.config.set_render_mode(RenderMode::Gouraud);
                 }
-                Event::KeyUp {
-                    keycode: Some(Keycode::D),
-                    ..
-                } => {
-                    println!("Disable back-face culling");
-                    self.config.set_backface_culling_enabled(false);
-                    return true;
+                InputEvent::KeyUp(Key::Num7) => {
+                    println!("Phong");
+                    self.config.set_render_mode(RenderMode::Phong);
+                }
+                InputEvent::KeyUp(key) if key == self.config.culling_key => {
+                    let enabled = !self.config.backface_culling_enabled();
+                    if enabled {
+                        println!("Enable back-face culling");
+                    } else {
+                        println!("Disable back-face culling");
+                    }
+                    self.config.set_backface_culling_enabled(enabled);
                 }
                 _ => {}
             }
         }
-        true
     }
 
-    fn render_buffer(&mut self, texture: &mut Texture) -> Result<(), UpdateTextureError> {
-        self.copy_buffer_to_canvas(texture)?;
+    fn render_buffer(&mut self) -> Result<(), String> {
+        self.backend.present(&self.color_buffer)
+    }
 
-        self.canvas.present();
+    /// Saves the displayed frame in the screenshot directory as `screenshot-<n>` where n is
+    /// the first number not already taken, returns the file's path
+    fn save_screenshot(&mut self) -> Result<PathBuf, Box<dyn Error>> {
+        let dir = self.config.screenshot_dir();
+        fs::create_dir_all(dir)?;
+
+        let extension = self.config.screenshot_format.extension();
+        let path = loop {
+            self.screenshot_count += 1;
+            let path = dir.join(format!(
+                "screenshot-{:04}.{}",
+                self.screenshot_count, extension
+            ));
+            if !path.exists() {
+                break path;
+            }
+        };
 
-        Ok(())
+        self.backend.save_screenshot(&path)?;
+        Ok(path)
     }
 
-    fn copy_buffer_to_canvas(&mut self, texture: &mut Texture) -> Result<(), UpdateTextureError> {
-        /*texture.update(None, self.color_buffer.pixels(), self.config.width * 4)?;
-        self.canvas.copy(texture, None, None).unwrap();*/
+    pub fn config(&self) -> &EngineConfig {
+        &self.config
+    }
 
-        texture
-            .with_lock(None, |buffer: &mut [u8], _pitch: usize| {
-                let pixels = self.color_buffer.pixels(); // Get the pixel data from color_buffer
-                buffer.copy_from_slice(pixels); // Copy pixel data into the locked texture buffer
-            })
-            .map_err(UpdateTextureError::SdlError)?;
+    /// Keys, buttons and mouse motion of the current frame
+    pub fn input(&self) -> &InputState {
+        &self.input
+    }
 
-        self.canvas.copy(texture, None, None).unwrap();
+    /// Seconds elapsed since the previous frame started
+    pub fn delta_time(&self) -> f64 {
+        self.delta_time
+    }
 
-        Ok(())
+    /// Moves the camera with the controller from the current frame's input
+    pub fn control_camera(&self, controller: &mut dyn CameraController, camera: &mut Camera) {
+        controller.update(camera, &self.input, self.delta_time);
     }
 
-    fn save_screenshot(&mut self, file_path: &str) -> Result<(), String> {
-        let (width, height) = self.canvas.output_size().map_err(|e| e.to_string())?;
+    pub fn color_buffer(&self) -> &ColorBuffer {
+        &self.color_buffer
+    }
 
-        // Read pixels from the canvas
-        let pixel_data = self
-            .canvas
-            .read_pixels(None, PixelFormatEnum::RGB888)
-            .map_err(|e| e.to_string())?;
+    pub fn depth_buffer(&self) -> &DepthBuffer {
+        &self.depth_buffer
+    }
 
-        // Convert the pixel data to an ImageBuffer
-        let img =
-            ImageBuffer::<Rgba<u8>, Vec<u8>>::from_raw(width as u32, height as u32, pixel_data)
-                .ok_or("Failed to create image buffer")?;
+    /// Draws every mesh of the scene from its active camera in the configured render mode,
+    /// nothing is drawn while the scene has no camera
+    pub fn render_scene(&mut self, scene: &Scene) {
+        let camera = match scene.active_camera() {
+            Some(camera) => camera,
+            None => return,
+        };
 
-        // Save the image as PNG
-        img.save(file_path).map_err(|e| e.to_string())?;
+        let mut commands = Vec::new();
+        for (mesh, world_matrix, normal_matrix) in scene.mesh_instances() {
+            self.render_mesh(
+                mesh,
+                &world_matrix,
+                &normal_matrix,
+                scene,
+                camera,
+                &mut commands,
+            );
+        }
 
-        Ok(())
+        let threads = match self.config.render_threads {
+            0 => thread::available_parallelism().map_or(1, |threads| threads.get()),
+            threads => threads,
+        };
+        self.execute(&commands, threads);
     }
 
-    pub fn config(&self) -> &EngineConfig {
-        &self.config
+    /// Records the draw commands of the mesh's visible triangles
+    fn render_mesh<'a>(
+        &self,
+        mesh: &'a Mesh,
+        world_matrix: &Matrix,
+        normal_matrix: &Matrix,
+        scene: &'a Scene,
+        camera: &Camera,
+        commands: &mut Vec<DrawCommand<'a>>,
+    ) {
+        let half_width = self.config.width as f64 / 2.0;
+        let half_height = self.config.height as f64 / 2.0;
+        let lights = scene.lights();
+
+        // Orthographic views see every point along +z rather than from the camera space
+        // origin
+        let parallel = camera.projection().projection_type().is_orthographic();
+
+        let transformed = TransformedMesh::new(mesh, world_matrix, normal_matrix, camera);
+
+        for face in 0..transformed.face_count() {
+            // Faces with indices out of range are skipped
+            let (triangle, clip_points) =
+                match (transformed.triangle(face), transformed.clip_points(face)) {
+                    (Some(triangle), Some(clip_points)) => (triangle, clip_points),
+                    _ => continue,
+                };
+            if self.config.backface_culling_enabled {
+                let visible = if parallel {
+                    triangle.should_cull_towards(Vec3(0.0, 0.0, 1.0))
+                } else {
+                    triangle.should_cull(Vec3::ZERO)
+                };
+                if !visible {
+                    continue;
+                }
+            }
+
+            let triangle = match self.config.render_mode {
+                RenderMode::Gouraud => lights.vertex_shaded_triangle(&triangle),
+                RenderMode::Phong => triangle,
+                _ => lights.shaded_triangle(&triangle),
+            };
+
+            let projected_tris =
+                triangle
+                    .clipped_from(clip_points, camera)
+                    .into_iter()
+                    .map(|tri| {
+                        tri.scale(Vec3(half_width, -half_height, 1.0))
+                            .translate(Vec3(half_width, half_height, 0.0))
+                    });
+            for tri in projected_tris {
+                self.push_triangle_commands(tri, mesh, scene, camera, commands);
+            }
+
+            // Outlines follow the polygons the faces come from rather than each triangle
+            let wireframe = matches!(
+                self.config.render_mode,
+                RenderMode::VerticesWireframe | RenderMode::Wireframe | RenderMode::SolidWireframe
+            );
+            if wireframe {
+                let to_screen = |point: Vec4| {
+                    let point = camera.normalize(point);
+                    Vec3(
+                        point.x() * half_width + half_width,
+                        -point.y() * half_height + half_height,
+                        camera.depth(&point),
+                    )
+                };
+                commands.extend(
+                    transformed
+                        .polygon_edges(face)
+                        .into_iter()
+                        .map(|(from, to)| DrawCommand::Line {
+                            from: to_screen(from),
+                            to: to_screen(to),
+                            color: WIREFRAME_COLOR,
+                        }),
+                );
+            }
+        }
+    }
+
+    /// Records the fill or vertex markers of a screen space triangle for the render mode
+    fn push_triangle_commands<'a>(
+        &self,
+        tri: Triangle,
+        mesh: &'a Mesh,
+        scene: &'a Scene,
+        camera: &Camera,
+        commands: &mut Vec<DrawCommand<'a>>,
+    ) {
+        let lights = scene.lights();
+
+        match self.config.render_mode {
+            RenderMode::VerticesWireframe => {
+                commands.extend([tri.a(), tri.b(), tri.c()].map(|point| DrawCommand::Rect {
+                    x: point.x() as usize,
+                    y: point.y() as usize,
+                    width: 4,
+                    height: 4,
+                    color: VERTEX_COLOR,
+                }));
+            }
+            RenderMode::Wireframe => {}
+            RenderMode::Solid | RenderMode::SolidWireframe => commands.push(DrawCommand::Fill {
+                triangle: tri,
+                shading: Shading::Flat,
+            }),
+            RenderMode::Textured => {
+                let shading = match mesh.texture_for(&tri) {
+                    Some(texture) => Shading::Textured(texture),
+                    None => Shading::Flat,
+                };
+                commands.push(DrawCommand::Fill {
+                    triangle: tri,
+                    shading,
+                });
+            }
+            RenderMode::Gouraud => commands.push(DrawCommand::Fill {
+                triangle: tri,
+                shading: Shading::Gouraud,
+            }),
+            RenderMode::Phong => {
+                let material = mesh.material_for(&tri).unwrap_or(scene.default_material());
+                commands.push(DrawCommand::Fill {
+                    triangle: tri,
+                    shading: Shading::Phong {
+                        lights,
+                        material,
+                        viewer_position: *camera.position(),
+                    },
+                });
+            }
+        }
+    }
+
+    /// Fills a screen space triangle whose points carry their depth in z, each pixel is
+    /// depth tested against the depth buffer using the configured depth function
+    pub fn draw_filled_triangle_depth(&mut self, a: Vec3, b: Vec3, c: Vec3, color: u32) {
+        self.fill(Triangle::new(a, b, c, color), Shading::Flat);
+    }
+
+    /// Fills a projected triangle with the texture, texture coordinates are interpolated
+    /// perspective correctly and texels are tinted by the triangle's color
+    pub fn draw_textured_triangle(&mut self, triangle: &Triangle, texture: &Texture) {
+        self.fill(*triangle, Shading::Textured(texture));
+    }
+
+    /// Fills a projected triangle with its color shaded by the light interpolated between
+    /// its points' shades
+    pub fn draw_gouraud_triangle(&mut self, triangle: &Triangle) {
+        self.fill(*triangle, Shading::Gouraud);
+    }
+
+    /// Fills a projected triangle lit per pixel with the Blinn-Phong model, its attributes
+    /// need world positions (see `Triangle::with_world_positions`) and normals
+    pub fn draw_phong_triangle(
+        &mut self,
+        triangle: &Triangle,
+        lights: &LightList,
+        material: &Material,
+        viewer_position: Vec3,
+    ) {
+        let shading = Shading::Phong {
+            lights,
+            material,
+            viewer_position,
+        };
+        self.fill(*triangle, shading);
+    }
+
+    fn fill(&mut self, triangle: Triangle, shading: Shading) {
+        self.execute(&[DrawCommand::Fill { triangle, shading }], 1);
+    }
+
+    /// Draws the commands in order, split into tiles drawn in parallel when given more
+    /// than one thread
+    fn execute(&mut self, commands: &[DrawCommand], threads: usize) {
+        let settings = RasterSettings {
+            width: self.config.width,
+            height: self.config.height,
+            rasterizer: self.config.rasterizer,
+            depth_func: self.config.depth_func,
+            depth_write_enabled: self.config.depth_write_enabled,
+        };
+        let colors = self.color_buffer.pixels_mut();
+        let depths = self.depth_buffer.depths_mut();
+
+        if threads > 1 {
+            tiles::render_tiled(commands, &settings, colors, depths, threads);
+        } else {
+            tiles::render(commands, &settings, colors, depths);
+        }
+    }
+
+    /// Ends the update loop once the current frame is done
+    pub fn stop(&mut self) {
+        self.running = false;
     }
 }
 
 impl ClearAuto for EngineCore {
     fn clear(&mut self) {
         self.color_buffer.clear(self.config.clear_color);
+        self.depth_buffer.clear();
     }
 }
 
@@ -404,4 +790,8 @@ impl Drawable for EngineCore {
     fn draw_line(&mut self, x0: f64, y0: f64, x1: f64, y1: f64, color: u32) {
         self.color_buffer.draw_line(x0, y0, x1, y1, color);
     }
+
+    fn draw_filled_triangle(&mut self, a: Vec2, b: Vec2, c: Vec2, color: u32) {
+        self.color_buffer.draw_filled_triangle(a, b, c, color);
+    }
 }
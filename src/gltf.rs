@@ -0,0 +1,432 @@
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::sync::Arc;
+
+use ::gltf::buffer::Data as BufferData;
+use ::gltf::image::{Data as ImageData, Format};
+use ::gltf::mesh::Mode;
+use vecx::{Vec2, Vec3, Vec4};
+
+use crate::material::Material;
+use crate::mesh::{Face, FaceAttributes, Mesh, Transform};
+use crate::quaternion::Quaternion;
+use crate::scene::{NodeId, Scene};
+use crate::texture::Texture;
+
+impl Scene {
+    /// Imports a glTF 2.0 file (`.gltf` along with its buffers and images, or `.glb`) in a
+    /// new node under `parent`. The nodes of the file's default scene become its children
+    /// with their names, transforms and hierarchy. Only local files and embedded data are
+    /// read. Skipped primitives and images are dropped, see `add_gltf_with_warnings`
+    pub fn add_gltf(
+        &mut self,
+        name: &str,
+        parent: Option<NodeId>,
+        path: &str,
+    ) -> Result<NodeId, Box<dyn Error>> {
+        self.add_gltf_with_warnings(name, parent, path)
+            .map(|(id, _)| id)
+    }
+
+    /// `add_gltf` along with what was skipped, primitives that aren't triangles or have no
+    /// positions and images in unsupported formats
+    pub fn add_gltf_with_warnings(
+        &mut self,
+        name: &str,
+        parent: Option<NodeId>,
+        path: &str,
+    ) -> Result<(NodeId, Vec<String>), Box<dyn Error>> {
+        let (document, buffers, images) = ::gltf::import(path)?;
+        let mut warnings = Vec::new();
+        let textures: Vec<Option<Arc<Texture>>> = images
+            .iter()
+            .enumerate()
+            .map(|(index, image)| match texture(image) {
+                Some(texture) => Some(Arc::new(texture)),
+                None => {
+                    warnings.push(format!(
+                        "skipping image {} with unsupported format {:?}",
+                        index, image.format
+                    ));
+                    None
+                }
+            })
+            .collect();
+
+        // Files without scenes get every node that isn't a child of another
+        let roots: Vec<::gltf::Node> = match document
+            .default_scene()
+            .or_else(|| document.scenes().next())
+        {
+            Some(scene) => scene.nodes().collect(),
+            None => {
+                let children: HashSet<usize> = document
+                    .nodes()
+                    .flat_map(|node| node.children().map(|child| child.index()))
+                    .collect();
+                document
+                    .nodes()
+                    .filter(|node| !children.contains(&node.index()))
+                    .collect()
+            }
+        };
+
+        let id = self.add_node(name, parent, None);
+        let mut meshes = HashMap::new();
+        for node in roots {
+            add_gltf_node(
+                self,
+                &node,
+                id,
+                &buffers,
+                &textures,
+                &mut meshes,
+                &mut warnings,
+            )?;
+        }
+        Ok((id, warnings))
+    }
+}
+
+/// Adds the node and its children under `parent`. Meshes are imported once, by their
+/// index in `meshes`, and copied for every other node using them
+fn add_gltf_node(
+    scene: &mut Scene,
+    node: &::gltf::Node,
+    parent: NodeId,
+    buffers: &[BufferData],
+    textures: &[Option<Arc<Texture>>],
+    meshes: &mut HashMap<usize, Mesh>,
+    warnings: &mut Vec<String>,
+) -> Result<(), Box<dyn Error>> {
+    let name = match node.name() {
+        Some(name) => name.to_string(),
+        None => format!("node {}", node.index()),
+    };
+    let mesh = match node.mesh() {
+        Some(mesh) => match meshes.get(&mesh.index()) {
+            Some(imported) => Some(imported.clone()),
+            None => {
+                let imported = gltf_mesh(&mesh, buffers, textures, warnings)?;
+                meshes.insert(mesh.index(), imported.clone());
+                Some(imported)
+            }
+        },
+        None => None,
+    };
+
+    let id = scene.add_node(&name, Some(parent), mesh);
+    let (translation, [x, y, z, w], scale) = node.transform().decomposed();
+    scene.node_mut(id).transform = Transform {
+        position: vec3(translation),
+        rotation: Quaternion::new(w as f64, x as f64, y as f64, z as f64),
+        scale: vec3(scale),
+    };
+
+    for child in node.children() {
+        add_gltf_node(scene, &child, id, buffers, textures, meshes, warnings)?;
+    }
+    Ok(())
+}
+
+/// Every triangle primitive of the mesh in a single mesh, with a material per glTF
+/// material used. Vertex colors tint the material's color
+fn gltf_mesh(
+    gltf_mesh: &::gltf::Mesh,
+    buffers: &[BufferData],
+    textures: &[Option<Arc<Texture>>],
+    warnings: &mut Vec<String>,
+) -> Result<Mesh, Box<dyn Error>> {
+    let mut mesh = Mesh::new(&[], &[]);
+    // glTF material index, None for the default material, to the mesh's
+    let mut materials: HashMap<Option<usize>, usize> = HashMap::new();
+    let mesh_name = match gltf_mesh.name() {
+        Some(name) => format!("'{}'", name),
+        None => gltf_mesh.index().to_string(),
+    };
+
+    for primitive in gltf_mesh.primitives() {
+        let reader = primitive.reader(|buffer| buffers.get(buffer.index()).map(|data| &data[..]));
+        let positions: Vec<[f32; 3]> = match reader.read_positions() {
+            Some(positions) => positions.collect(),
+            None => {
+                warnings.push(format!(
+                    "skipping primitive {} of mesh {} without positions",
+                    primitive.index(),
+                    mesh_name
+                ));
+                continue;
+            }
+        };
+
+        let indices: Vec<u32> = match reader.read_indices() {
+            Some(indices) => indices.into_u32().collect(),
+            None => (0..positions.len() as u32).collect(),
+        };
+        let triangles: Vec<[u32; 3]> = match primitive.mode() {
+            Mode::Triangles => indices
+                .chunks_exact(3)
+                .map(|t| [t[0], t[1], t[2]])
+                .collect(),
+            // Every other triangle of a strip is flipped to keep the winding
+            Mode::TriangleStrip => (2..indices.len())
+                .map(|i| match i % 2 {
+                    0 => [indices[i - 2], indices[i - 1], indices[i]],
+                    _ => [indices[i - 1], indices[i - 2], indices[i]],
+                })
+                .collect(),
+            Mode::TriangleFan => (2..indices.len())
+                .map(|i| [indices[0], indices[i - 1], indices[i]])
+                .collect(),
+            mode => {
+                warnings.push(format!(
+                    "skipping {:?} primitive {} of mesh {}",
+                    mode,
+                    primitive.index(),
+                    mesh_name
+                ));
+                continue;
+            }
+        };
+
+        let gltf_material = primitive.material();
+        let material = *materials.entry(gltf_material.index()).or_insert_with(|| {
+            mesh.materials
+                .push(Arc::new(material(&gltf_material, textures)));
+            mesh.materials.len() - 1
+        });
+        let material_color = mesh.materials[material].color();
+
+        // Face indices are 1-based
+        let vertex_base = mesh.vertices.len() + 1;
+        let uv_base = mesh.uvs.len() + 1;
+        let normal_base = mesh.normals.len() + 1;
+        let color_base = mesh.colors.len() + 1;
+
+        let vertex_count = positions.len();
+        mesh.vertices.extend(positions.into_iter().map(vec3));
+        let has_uvs = match reader.read_tex_coords(0) {
+            Some(uvs) => {
+                // glTF's v points down
+                let uvs = uvs.into_f32().map(|[u, v]| Vec2(u as f64, 1.0 - v as f64));
+                mesh.uvs.extend(uvs);
+                true
+            }
+            None => false,
+        };
+        let has_normals = match reader.read_normals() {
+            Some(normals) => {
+                mesh.normals.extend(normals.map(vec3));
+                true
+            }
+            None => false,
+        };
+        let has_colors = match reader.read_colors(0) {
+            Some(colors) => {
+                // Vertices missing a color are white
+                let mut colors: Vec<Vec4> = colors
+                    .into_rgba_f32()
+                    .map(|[r, g, b, a]| Vec4(r as f64, g as f64, b as f64, a as f64))
+                    .collect();
+                colors.resize(vertex_count, Vec4(1.0, 1.0, 1.0, 1.0));
+                mesh.colors.extend(colors);
+                true
+            }
+            None => false,
+        };
+
+        for triangle in triangles {
+            let [a, b, c] = triangle.map(|index| index as usize);
+
+            mesh.faces.push(Face(
+                vertex_base + a,
+                vertex_base + b,
+                vertex_base + c,
+                material_color,
+                FaceAttributes {
+                    uvs: has_uvs.then_some([uv_base + a, uv_base + b, uv_base + c]),
+                    normals: has_normals.then_some([
+                        normal_base + a,
+                        normal_base + b,
+                        normal_base + c,
+                    ]),
+                    colors: has_colors.then_some([color_base + a, color_base + b, color_base + c]),
+                    material: Some(material),
+                    ..FaceAttributes::NONE
+                },
+            ));
+        }
+    }
+
+    mesh.validate()?;
+    if mesh.normals.is_empty() {
+        mesh.compute_normals();
+    }
+    Ok(mesh)
+}
+
+/// Base color factor and texture of the metallic roughness model
+fn material(material: &::gltf::Material, textures: &[Option<Arc<Texture>>]) -> Material {
+    let pbr = material.pbr_metallic_roughness();
+    let [r, g, b, a] = pbr.base_color_factor();
+
+    let mut result = Material::new(material.name().unwrap_or("default"));
+    result.diffuse = Vec3(r as f64, g as f64, b as f64);
+    result.opacity = a as f64;
+    result.diffuse_texture = pbr
+        .base_color_texture()
+        .and_then(|info| textures.get(info.texture().source().index()).cloned())
+        .flatten();
+    result
+}
+
+/// ARGB texture of an 8 bits per channel image, None for other formats
+fn texture(image: &ImageData) -> Option<Texture> {
+    let channels = match image.format {
+        Format::R8 => 1,
+        Format::R8G8 => 2,
+        Format::R8G8B8 => 3,
+        Format::R8G8B8A8 => 4,
+        _ => return None,
+    };
+
+    let pixels = image
+        .pixels
+        .chunks_exact(channels)
+        .map(|pixel| {
+            let (r, g, b, a) = match *pixel {
+                [l] => (l, l, l, 0xFF),
+                [l, a] => (l, l, l, a),
+                [r, g, b] => (r, g, b, 0xFF),
+                [r, g, b, a] => (r, g, b, a),
+                _ => unreachable!(),
+            };
+            u32::from_be_bytes([a, r, g, b])
+        })
+        .collect();
+
+    Some(Texture::new(
+        image.width as usize,
+        image.height as usize,
+        pixels,
+    ))
+}
+
+fn vec3([x, y, z]: [f32; 3]) -> Vec3 {
+    Vec3(x as f64, y as f64, z as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    const TRIANGLE: &str = r#"{
+        "asset": { "version": "2.0" },
+        "scene": 0,
+        "scenes": [{ "nodes": [0] }],
+        "nodes": [{ "name": "node", "mesh": 0 }],
+        "meshes": [{
+            "name": "triangle",
+            "primitives": [
+                { "attributes": { "POSITION": 0, "COLOR_0": 1 } },
+                { "attributes": { "POSITION": 0 }, "mode": 0 }
+            ]
+        }],
+        "buffers": [{ "uri": "triangle.bin", "byteLength": 84 }],
+        "bufferViews": [
+            { "buffer": 0, "byteLength": 36 },
+            { "buffer": 0, "byteOffset": 36, "byteLength": 48 }
+        ],
+        "accessors": [
+            {
+                "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3",
+                "min": [0, 0, 0], "max": [1, 1, 0]
+            },
+            { "bufferView": 1, "componentType": 5126, "count": 3, "type": "VEC4" }
+        ]
+    }"#;
+
+    const POSITIONS: [[f32; 3]; 3] = [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]];
+    const COLORS: [[f32; 4]; 3] = [
+        [1.0, 0.0, 0.0, 1.0],
+        [0.0, 1.0, 0.0, 1.0],
+        [0.0, 0.0, 1.0, 0.5],
+    ];
+
+    /// Imports the file in a new scene, next to the triangle's buffer
+    fn import(gltf: &str, file_name: &str) -> (Scene, NodeId, Vec<String>) {
+        let bytes: Vec<u8> = POSITIONS
+            .iter()
+            .flatten()
+            .chain(COLORS.iter().flatten())
+            .flat_map(|value| value.to_le_bytes())
+            .collect();
+
+        let directory = std::env::temp_dir().join(format!("{}-{}", file_name, std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        fs::write(directory.join("triangle.bin"), bytes).unwrap();
+        let path = directory.join(file_name);
+        fs::write(&path, gltf).unwrap();
+        let mut scene = Scene::new();
+        let result = scene.add_gltf_with_warnings("file", None, &path.to_string_lossy());
+        fs::remove_dir_all(&directory).unwrap();
+
+        let (id, warnings) = result.unwrap();
+        (scene, id, warnings)
+    }
+
+    #[test]
+    fn vertex_colors_are_kept_per_corner() {
+        let (scene, id, warnings) = import(TRIANGLE, "triangle.gltf");
+        assert_eq!(warnings, ["skipping Points primitive 1 of mesh 'triangle'"]);
+
+        let node = scene.node(scene.node(id).children()[0]);
+        let mesh = node.mesh().unwrap();
+        let expected: Vec<Vec4> = COLORS
+            .iter()
+            .map(|&[r, g, b, a]| Vec4(r as f64, g as f64, b as f64, a as f64))
+            .collect();
+        assert_eq!(mesh.colors, expected);
+        assert_eq!(mesh.faces.len(), 1);
+        assert_eq!(mesh.faces[0].attributes().colors, Some([1, 2, 3]));
+        assert_eq!(mesh.faces[0].color(), 0xFFFFFFFF);
+
+        let corners: Vec<Vec4> = mesh
+            .triangles()
+            .next()
+            .unwrap()
+            .4
+            .map(|corner| corner.color)
+            .to_vec();
+        assert_eq!(corners, expected);
+    }
+
+    #[test]
+    fn meshes_used_by_several_nodes_are_imported_once() {
+        let gltf = TRIANGLE
+            .replace(r#""nodes": [0]"#, r#""nodes": [0, 1]"#)
+            .replace(
+                r#""nodes": [{ "name": "node", "mesh": 0 }]"#,
+                r#""nodes": [{ "name": "a", "mesh": 0 }, { "name": "b", "mesh": 0 }]"#,
+            );
+        let (scene, id, warnings) = import(&gltf, "instanced.gltf");
+
+        // The skipped primitive is only reported by the first import
+        assert_eq!(warnings, ["skipping Points primitive 1 of mesh 'triangle'"]);
+        let children = scene.node(id).children();
+        assert_eq!(children.len(), 2);
+        let (a, b) = (scene.node(children[0]), scene.node(children[1]));
+        assert_eq!((a.name(), b.name()), ("a", "b"));
+        let (a, b) = (a.mesh().unwrap(), b.mesh().unwrap());
+        assert_eq!(a.vertices, b.vertices);
+        assert_eq!(a.colors, b.colors);
+        assert_eq!(a.faces.len(), b.faces.len());
+        assert_eq!(a.materials.len(), 1);
+        assert_eq!(b.materials.len(), 1);
+        for (a, b) in a.materials.iter().zip(&b.materials) {
+            assert!(Arc::ptr_eq(a, b));
+        }
+    }
+}
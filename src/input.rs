@@ -0,0 +1,160 @@
+use std::collections::HashSet;
+
+/// Keys the engine knows about, independent of the windowing backend
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Key {
+    A,
+    B,
+    C,
+    D,
+    E,
+    F,
+    G,
+    H,
+    I,
+    J,
+    K,
+    L,
+    M,
+    N,
+    O,
+    P,
+    Q,
+    R,
+    S,
+    T,
+    U,
+    V,
+    W,
+    X,
+    Y,
+    Z,
+    Num0,
+    Num1,
+    Num2,
+    Num3,
+    Num4,
+    Num5,
+    Num6,
+    Num7,
+    Num8,
+    Num9,
+    Escape,
+    Space,
+    Return,
+    Tab,
+    Left,
+    Right,
+    Up,
+    Down,
+    LShift,
+    RShift,
+    LCtrl,
+    RCtrl,
+    F1,
+    F2,
+    F3,
+    F4,
+    F5,
+    F6,
+    F7,
+    F8,
+    F9,
+    F10,
+    F11,
+    F12,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MouseButton {
+    Left,
+    Middle,
+    Right,
+}
+
+/// Input events forwarded by a backend to the engine
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InputEvent {
+    Quit,
+    KeyDown(Key),
+    KeyUp(Key),
+    /// Relative motion in pixels, y grows downwards
+    MouseMotion {
+        dx: f64,
+        dy: f64,
+    },
+    MouseButtonDown(MouseButton),
+    MouseButtonUp(MouseButton),
+    /// Scrolled amount, positive away from the user
+    MouseWheel(f64),
+}
+
+/// Keys and buttons currently held down, plus what happened during the current frame
+#[derive(Debug, Default)]
+pub struct InputState {
+    keys_down: HashSet<Key>,
+    keys_released: HashSet<Key>,
+    buttons_down: HashSet<MouseButton>,
+    mouse_motion: (f64, f64),
+    wheel: f64,
+}
+
+impl InputState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_key_down(&self, key: Key) -> bool {
+        self.keys_down.contains(&key)
+    }
+
+    /// Whether the key was released during the current frame
+    pub fn was_key_released(&self, key: Key) -> bool {
+        self.keys_released.contains(&key)
+    }
+
+    pub fn is_button_down(&self, button: MouseButton) -> bool {
+        self.buttons_down.contains(&button)
+    }
+
+    /// Mouse motion accumulated during the current frame
+    pub fn mouse_motion(&self) -> (f64, f64) {
+        self.mouse_motion
+    }
+
+    /// Wheel scrolling accumulated during the current frame
+    pub fn wheel(&self) -> f64 {
+        self.wheel
+    }
+
+    /// Forgets the previous frame's motion, scrolling and releases, held keys are kept
+    pub(crate) fn begin_frame(&mut self) {
+        self.keys_released.clear();
+        self.mouse_motion = (0.0, 0.0);
+        self.wheel = 0.0;
+    }
+
+    pub(crate) fn handle(&mut self, event: &InputEvent) {
+        match *event {
+            InputEvent::KeyDown(key) => {
+                self.keys_down.insert(key);
+            }
+            InputEvent::KeyUp(key) => {
+                self.keys_down.remove(&key);
+                self.keys_released.insert(key);
+            }
+            InputEvent::MouseMotion { dx, dy } => {
+                self.mouse_motion.0 += dx;
+                self.mouse_motion.1 += dy;
+            }
+            InputEvent::MouseButtonDown(button) => {
+                self.buttons_down.insert(button);
+            }
+            InputEvent::MouseButtonUp(button) => {
+                self.buttons_down.remove(&button);
+            }
+            InputEvent::MouseWheel(amount) => self.wheel += amount,
+            InputEvent::Quit => {}
+        }
+    }
+}
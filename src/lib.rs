@@ -1,14 +1,50 @@
+mod backend;
 mod buffer;
+mod clipping;
+mod controller;
 mod engine;
+#[cfg(feature = "gltf")]
+mod gltf;
+mod input;
 mod lighting;
+mod material;
 mod mesh;
+mod model;
+mod obj;
 mod projection;
+mod quaternion;
+mod raster;
+mod scene;
+mod texture;
+mod tiles;
 
-pub use buffer::{ClearAuto, ClearColor, Drawable};
-pub use engine::{Engine, EngineConfig, EngineConfigParams, EngineCore, RenderMode};
-pub use lighting::GlobalLight;
-pub use mesh::{Mesh, Triangle};
-pub use projection::{Camera, CameraProjection};
+#[cfg(feature = "sdl")]
+pub use backend::SdlBackend;
+pub use backend::{Backend, HeadlessBackend};
+pub use buffer::{ClearAuto, ClearColor, ColorBuffer, DepthBuffer, DepthFunc, Drawable};
+pub use controller::{CameraController, FlyController, OrbitController};
+pub use engine::{
+    Engine, EngineConfig, EngineConfigParams, EngineCore, Rasterizer, RenderMode, ScreenshotFormat,
+};
+pub use input::{InputEvent, InputState, Key, MouseButton};
+pub use lighting::{Attenuation, GlobalLight, Light, LightList, PointLight, SpotLight};
+pub use material::Material;
+pub use mesh::{
+    Face, FaceAttributes, FaceIndexError, Mesh, Transform, TransformedMesh, Triangle,
+    VertexAttributes,
+};
+pub use model::{Model, ModelPart};
+pub use obj::ObjError;
+pub use projection::{Camera, CameraProjection, ProjectionType};
+pub use quaternion::{EulerOrder, Quaternion};
+pub use raster::{rasterize_triangle, Barycentric};
+pub use scene::{NodeId, Scene, SceneNode};
+pub use texture::Texture;
 
 pub mod prelude;
 pub mod utils;
+
+// Compiles the README's examples
+#[doc = include_str!("../README.md")]
+#[cfg(doctest)]
+pub struct ReadmeDoctests;
@@ -1,9 +1,12 @@
 use vecx::{Vec3, VecX};
 
+use crate::material::Material;
 use crate::Triangle;
 
+#[derive(Clone, Copy, Debug)]
 pub struct GlobalLight {
     direction: Vec3,
+    color: Vec3,
 }
 
 impl GlobalLight {
@@ -15,9 +18,19 @@ impl GlobalLight {
     pub fn new(direction: Vec3) -> Self {
         GlobalLight {
             direction: Vec3(direction.0, -direction.1, -direction.2),
+            color: Vec3(1.0, 1.0, 1.0),
         }
     }
 
+    /// Light color, white by default
+    pub fn color(&self) -> Vec3 {
+        self.color
+    }
+
+    pub fn set_color(&mut self, color: Vec3) {
+        self.color = color;
+    }
+
     pub fn light_factor(&self, face_normal: &Vec3) -> f64 {
         f64::min(0.0, self.direction.dot(face_normal)) * -1.0
     }
@@ -35,6 +48,371 @@ impl GlobalLight {
     pub fn shaded_triangle(&self, triangle: &Triangle) -> Triangle {
         let light_factor = self.light_factor(&triangle.normal());
         let color = self.lit_color(triangle.color(), light_factor);
-        Triangle(triangle.0, triangle.1, triangle.2, color)
+        Triangle(
+            triangle.0, triangle.1, triangle.2, color, triangle.4, triangle.5,
+        )
+    }
+
+    /// Lights each point from its own normal and stores the result in its shade, leaving
+    /// the triangle's color untouched so it can be shaded per pixel
+    pub fn vertex_shaded_triangle(&self, triangle: &Triangle) -> Triangle {
+        let mut shaded = *triangle;
+        for attributes in shaded.4.iter_mut() {
+            let light_factor = self.light_factor(&attributes.normal);
+            attributes.shade = Vec3(light_factor, light_factor, light_factor);
+        }
+        shaded
+    }
+}
+
+/// Light falling off with distance as 1 / (constant + linear * d + quadratic * d²)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Attenuation {
+    pub constant: f64,
+    pub linear: f64,
+    pub quadratic: f64,
+}
+
+impl Attenuation {
+    /// Same intensity at any distance
+    pub const NONE: Attenuation = Attenuation {
+        constant: 1.0,
+        linear: 0.0,
+        quadratic: 0.0,
+    };
+
+    pub fn factor(&self, distance: f64) -> f64 {
+        let divisor = self.constant + self.linear * distance + self.quadratic * distance * distance;
+        if divisor <= 0.0 {
+            return 1.0;
+        }
+        f64::min(1.0, 1.0 / divisor)
+    }
+}
+
+impl Default for Attenuation {
+    /// Covers roughly 50 units
+    fn default() -> Self {
+        Attenuation {
+            constant: 1.0,
+            linear: 0.09,
+            quadratic: 0.032,
+        }
+    }
+}
+
+/// Light shining in every direction from a point
+#[derive(Clone, Copy, Debug)]
+pub struct PointLight {
+    pub position: Vec3,
+    pub color: Vec3,
+    pub attenuation: Attenuation,
+}
+
+impl PointLight {
+    pub fn new(position: Vec3, color: Vec3) -> Self {
+        PointLight {
+            position,
+            color,
+            attenuation: Attenuation::default(),
+        }
+    }
+}
+
+/// Light shining from a point inside a cone, full intensity within the inner angle and
+/// fading out towards the outer angle. Angles are measured from the axis, in radians
+#[derive(Clone, Copy, Debug)]
+pub struct SpotLight {
+    pub position: Vec3,
+    direction: Vec3,
+    pub color: Vec3,
+    pub inner_angle: f64,
+    pub outer_angle: f64,
+    pub attenuation: Attenuation,
+}
+
+impl SpotLight {
+    pub fn new(
+        position: Vec3,
+        direction: Vec3,
+        color: Vec3,
+        inner_angle: f64,
+        outer_angle: f64,
+    ) -> Self {
+        SpotLight {
+            position,
+            direction: direction.normalized(),
+            color,
+            inner_angle,
+            outer_angle,
+            attenuation: Attenuation::default(),
+        }
+    }
+
+    pub fn direction(&self) -> Vec3 {
+        self.direction
+    }
+
+    pub fn set_direction(&mut self, direction: Vec3) {
+        self.direction = direction.normalized();
+    }
+
+    /// 1 inside the inner cone, 0 outside the outer cone and smooth in between
+    fn cone_factor(&self, from_light: &Vec3) -> f64 {
+        let cos_angle = self.direction.dot(from_light);
+        let cos_inner = self.inner_angle.cos();
+        let cos_outer = self.outer_angle.max(self.inner_angle).cos();
+
+        if cos_angle >= cos_inner {
+            return 1.0;
+        }
+        if cos_angle <= cos_outer {
+            return 0.0;
+        }
+
+        let t = (cos_angle - cos_outer) / (cos_inner - cos_outer);
+        t * t * (3.0 - 2.0 * t)
+    }
+}
+
+/// Any light that can be put in a `LightList`
+#[derive(Clone, Copy, Debug)]
+pub enum Light {
+    Global(GlobalLight),
+    Point(PointLight),
+    Spot(SpotLight),
+}
+
+impl Light {
+    /// Direction from the surface point to the light and the light's color once attenuated,
+    /// None when the point is out of reach
+    pub fn incoming(&self, position: Vec3) -> Option<(Vec3, Vec3)> {
+        let (to_light, intensity, color) = match self {
+            Light::Global(light) => (
+                Vec3(
+                    -light.direction.x(),
+                    -light.direction.y(),
+                    -light.direction.z(),
+                ),
+                1.0,
+                light.color,
+            ),
+            Light::Point(light) => {
+                let offset = light.position - position;
+                let distance = offset.dot(&offset).sqrt();
+                if distance == 0.0 {
+                    return None;
+                }
+                let to_light = Vec3(
+                    offset.x() / distance,
+                    offset.y() / distance,
+                    offset.z() / distance,
+                );
+                (to_light, light.attenuation.factor(distance), light.color)
+            }
+            Light::Spot(light) => {
+                let offset = light.position - position;
+                let distance = offset.dot(&offset).sqrt();
+                if distance == 0.0 {
+                    return None;
+                }
+                let to_light = Vec3(
+                    offset.x() / distance,
+                    offset.y() / distance,
+                    offset.z() / distance,
+                );
+                let from_light = Vec3(-to_light.x(), -to_light.y(), -to_light.z());
+                let intensity = light.attenuation.factor(distance) * light.cone_factor(&from_light);
+                (to_light, intensity, light.color)
+            }
+        };
+
+        if intensity <= 0.0 {
+            return None;
+        }
+
+        Some((
+            to_light,
+            Vec3(
+                color.x() * intensity,
+                color.y() * intensity,
+                color.z() * intensity,
+            ),
+        ))
+    }
+}
+
+impl From<GlobalLight> for Light {
+    fn from(light: GlobalLight) -> Self {
+        Light::Global(light)
+    }
+}
+
+impl From<PointLight> for Light {
+    fn from(light: PointLight) -> Self {
+        Light::Point(light)
+    }
+}
+
+impl From<SpotLight> for Light {
+    fn from(light: SpotLight) -> Self {
+        Light::Spot(light)
+    }
+}
+
+/// Every light of a scene, their colored contributions are summed per face, per vertex or
+/// per pixel depending on the render mode
+pub struct LightList {
+    lights: Vec<Light>,
+    ambient: Vec3,
+}
+
+impl LightList {
+    pub fn new() -> Self {
+        LightList {
+            lights: Vec::new(),
+            ambient: Vec3(0.1, 0.1, 0.1),
+        }
+    }
+
+    pub fn add<L: Into<Light>>(&mut self, light: L) {
+        self.lights.push(light.into());
+    }
+
+    pub fn lights(&self) -> &[Light] {
+        &self.lights
+    }
+
+    pub fn lights_mut(&mut self) -> &mut Vec<Light> {
+        &mut self.lights
+    }
+
+    /// Light reaching every surface regardless of its orientation, scaled by the material's Ka
+    pub fn ambient(&self) -> Vec3 {
+        self.ambient
+    }
+
+    pub fn set_ambient(&mut self, ambient: Vec3) {
+        self.ambient = ambient;
+    }
+
+    /// Ambient and diffuse light reaching a surface point
+    pub fn diffuse_light(&self, position: Vec3, normal: &Vec3) -> Vec3 {
+        let mut light = self.ambient;
+        for (to_light, color) in self.lights.iter().filter_map(|l| l.incoming(position)) {
+            let diffuse = f64::max(0.0, normal.dot(&to_light));
+            light += Vec3(
+                color.x() * diffuse,
+                color.y() * diffuse,
+                color.z() * diffuse,
+            );
+        }
+        light
+    }
+
+    /// Blinn-Phong reflection at a surface point, returns the light multiplying the surface's
+    /// color (ambient and diffuse) and the specular light added on top of it
+    pub fn blinn_phong(
+        &self,
+        position: Vec3,
+        normal: &Vec3,
+        to_viewer: &Vec3,
+        material: &Material,
+    ) -> (Vec3, Vec3) {
+        let ambient = material.ambient;
+        let mut diffuse_light = Vec3(
+            ambient.x() * self.ambient.x(),
+            ambient.y() * self.ambient.y(),
+            ambient.z() * self.ambient.z(),
+        );
+        let mut specular_light = Vec3::zero();
+
+        for (to_light, color) in self.lights.iter().filter_map(|l| l.incoming(position)) {
+            let diffuse = f64::max(0.0, normal.dot(&to_light));
+            if diffuse == 0.0 {
+                continue;
+            }
+            diffuse_light += Vec3(
+                color.x() * diffuse,
+                color.y() * diffuse,
+                color.z() * diffuse,
+            );
+
+            let half_way = to_light + *to_viewer;
+            if half_way == Vec3::ZERO {
+                continue;
+            }
+
+            let specular =
+                f64::max(0.0, normal.dot(&half_way.normalized())).powf(material.shininess);
+            specular_light += Vec3(
+                material.specular.x() * color.x() * specular,
+                material.specular.y() * color.y() * specular,
+                material.specular.z() * color.z() * specular,
+            );
+        }
+
+        (diffuse_light, specular_light)
+    }
+
+    /// Lights the whole face from its normal at its center, both taken from the triangle's
+    /// attributes' world positions (see `Triangle::with_world_positions`) so the points
+    /// themselves may already be in camera space
+    pub fn shaded_triangle(&self, triangle: &Triangle) -> Triangle {
+        let positions = triangle.attributes().map(|attributes| attributes.position);
+        let normal = Triangle::new(positions[0], positions[1], positions[2], 0).normal();
+        let center = Vec3(
+            (positions[0].x() + positions[1].x() + positions[2].x()) / 3.0,
+            (positions[0].y() + positions[1].y() + positions[2].y()) / 3.0,
+            (positions[0].z() + positions[1].z() + positions[2].z()) / 3.0,
+        );
+
+        let light = self.diffuse_light(center, &normal);
+        let color = shaded_color(triangle.color(), light);
+        Triangle(
+            triangle.0, triangle.1, triangle.2, color, triangle.4, triangle.5,
+        )
     }
+
+    /// Lights each point from its own normal and stores the result in its shade, like
+    /// `GlobalLight::vertex_shaded_triangle`
+    pub fn vertex_shaded_triangle(&self, triangle: &Triangle) -> Triangle {
+        let mut shaded = *triangle;
+        for attributes in shaded.4.iter_mut() {
+            attributes.shade = self.diffuse_light(attributes.position, &attributes.normal);
+        }
+        shaded
+    }
+}
+
+impl Default for LightList {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Shades the color then adds the specular light, alpha is kept
+pub(crate) fn lit_specular_color(color: u32, shade: Vec3, specular: Vec3) -> u32 {
+    let [a, r, g, b] = shaded_color(color, shade).to_be_bytes();
+    let channel = |c: u8, light: f64| (c as f64 + light.max(0.0) * 255.0).min(255.0) as u8;
+
+    u32::from_be_bytes([
+        a,
+        channel(r, specular.x()),
+        channel(g, specular.y()),
+        channel(b, specular.z()),
+    ])
+}
+
+/// Multiplies the color's channels by the matching shade component, alpha is kept
+pub(crate) fn shaded_color(color: u32, shade: Vec3) -> u32 {
+    let [a, r, g, b] = color.to_be_bytes();
+    let channel = |c: u8, factor: f64| (c as f64 * factor.clamp(0.0, 1.0)) as u8;
+
+    u32::from_be_bytes([
+        a,
+        channel(r, shade.x()),
+        channel(g, shade.y()),
+        channel(b, shade.z()),
+    ])
 }
@@ -1,7 +1,11 @@
 use std::f64::consts::PI;
+use std::sync::Arc;
 
-use renderer3d::{prelude::*, Camera, GlobalLight, Mesh};
-use vecx::{Vec2, Vec3, VecX};
+use renderer3d::{
+    prelude::*, Camera, CameraController, FlyController, GlobalLight, Key, Mesh, OrbitController,
+    PointLight, Texture,
+};
+use vecx::{Vec3, VecX};
 
 pub fn main() {
     let mut eng = Engine::build(EngineConfig::new(EngineConfigParams {
@@ -15,147 +19,109 @@ pub fn main() {
     let cam_pos = Vec3(0.0, 0.0, -5.0);
 
     //let mut cube = Mesh::cube();
-    let mut mesh = Mesh::load_obj("./assets/f22.obj").unwrap();
+    let (mut mesh, warnings) = Mesh::load_obj_with_warnings("./assets/f22.obj").unwrap();
+    for warning in warnings {
+        eprintln!("{}", warning);
+    }
     mesh.transform.position = Vec3(0.0, 0.0, 0.0);
-    let camera = Camera::new(
+    mesh.texture = Some(Arc::new(Texture::checkerboard(
+        256, 16, 0xFFFFFFFF, 0xFF555555,
+    )));
+    let aspect_ratio = eng.config().aspect_ratio();
+    let target_distance = cam_pos.dot(&cam_pos).sqrt();
+    let projection_for = |projection_type: ProjectionType| match projection_type {
+        ProjectionType::Perspective => CameraProjection::perspective(aspect_ratio, fov, 0.1, 100.0),
+        ProjectionType::Orthographic => CameraProjection::orthographic(
+            -3.0 / aspect_ratio,
+            3.0 / aspect_ratio,
+            -3.0,
+            3.0,
+            0.1,
+            100.0,
+        ),
+        ProjectionType::Isometric => CameraProjection::isometric(aspect_ratio, 3.0, 0.1, 100.0),
+        ProjectionType::Dimetric => CameraProjection::dimetric(aspect_ratio, 3.0, 0.1, 100.0),
+    };
+    let camera = Camera::look_at(
         cam_pos,
-        CameraProjection::perspective(eng.config().aspect_ratio(), fov, 0.1, 100.0),
+        Vec3(0.0, 0.0, 0.0),
+        Vec3(0.0, 1.0, 0.0),
+        projection_for(ProjectionType::Perspective),
     );
-    let wireframe_color = 0xFF00FF00;
-
-    let half_width = eng.config().width() as f64 / 2.0;
-    let half_height = eng.config().height() as f64 / 2.0;
-
-    let global_light = GlobalLight::new(Vec3(0.0, -1.0, 1.0).normalized());
+    let mut projection_type = ProjectionType::Perspective;
+    let mut orbit_controller = OrbitController::new(Vec3(0.0, 0.0, 0.0), target_distance);
+    let mut fly_controller = FlyController::new(5.0);
+    let mut flying = false;
+
+    let mut scene = Scene::new();
+    scene.add_mesh("f22", mesh);
+
+    // A cube circling the plane, carried around by its rotating parent
+    let orbit = scene.add_node("orbit", None, None);
+    let mut satellite = Mesh::cube();
+    satellite.transform.scale = Vec3(0.2, 0.2, 0.2);
+    let satellite = scene.add_node("satellite", Some(orbit), Some(satellite));
+    scene.node_mut(satellite).transform.position = Vec3(3.0, 0.0, 0.0);
+    scene.add_camera("main", camera);
+    scene
+        .lights_mut()
+        .add(GlobalLight::new(Vec3(0.0, -1.0, 1.0).normalized()));
+    scene
+        .lights_mut()
+        .add(PointLight::new(Vec3(3.0, 2.0, -3.0), Vec3(1.0, 0.6, 0.3)));
 
     println!("Start update: {}", eng.config().aspect_ratio());
     eng.on_update(&mut |eng| {
         eng.draw_grid(10, Some(0xFF333333));
-        mesh.transform.rotation += Vec3(0.01, 0.00, 0.0);
-        //mesh.transform.scale += Vec3(0.001, 0.001, 0.001);
-        //mesh.transform.position += Vec3(0.01, 0.0, 0.0);
-
-        let mut projected_tris: Vec<Triangle> = mesh
-            .triangles()
-            .map(|triangle| {
-                global_light.shaded_triangle(
-                    &triangle.matrix_transform(&mesh.transform).translate(Vec3(
-                        0.0,
-                        0.0,
-                        -cam_pos.z(),
-                    )),
-                )
-            })
-            .filter(|triangle| {
-                !eng.config().backface_culling_enabled() || triangle.should_cull(cam_pos)
-            })
-            .map(|tri| {
-                tri.projected(&camera)
-                    .scale(Vec3(half_width, -half_height, 1.0))
-                    .translate(Vec3(half_width, half_height, 0.0))
-            })
-            .collect();
-
-        projected_tris.sort_by(|a, b| b.avg_z().total_cmp(&a.avg_z()));
-
-        projected_tris.iter().for_each(|tri| {
-            let a = tri.a();
-            let b = tri.b();
-            let c = tri.c();
-
-            match eng.config().render_mode() {
-                RenderMode::VerticesWireframe => {
-                    eng.draw_rect(a.x() as usize, a.y() as usize, 4, 4, 0xFFFF0000);
-                    eng.draw_rect(b.x() as usize, b.y() as usize, 4, 4, 0xFFFF0000);
-                    eng.draw_rect(c.x() as usize, c.y() as usize, 4, 4, 0xFFFF0000);
-
-                    eng.draw_line(a.x(), a.y(), b.x(), b.y(), wireframe_color);
-                    eng.draw_line(b.x(), b.y(), c.x(), c.y(), wireframe_color);
-                    eng.draw_line(c.x(), c.y(), a.x(), a.y(), wireframe_color);
-                }
-                RenderMode::Wireframe => {
-                    eng.draw_line(a.x(), a.y(), b.x(), b.y(), wireframe_color);
-                    eng.draw_line(b.x(), b.y(), c.x(), c.y(), wireframe_color);
-                    eng.draw_line(c.x(), c.y(), a.x(), a.y(), wireframe_color);
-                }
-                RenderMode::Solid => {
-                    draw_filled_triangle(a.into(), b.into(), c.into(), tri.color(), eng);
-                }
-                RenderMode::SolidWireframe => {
-                    draw_filled_triangle(a.into(), b.into(), c.into(), tri.color(), eng);
-                    eng.draw_line(a.x(), a.y(), b.x(), b.y(), wireframe_color);
-                    eng.draw_line(b.x(), b.y(), c.x(), c.y(), wireframe_color);
-                    eng.draw_line(c.x(), c.y(), a.x(), a.y(), wireframe_color);
+        scene
+            .node_mut(orbit)
+            .transform
+            .rotate(Vec3(0.0, 1.0, 0.0), 0.02);
+        scene
+            .node_mut(satellite)
+            .transform
+            .rotate_local(Vec3(1.0, 0.0, 0.0), 0.05);
+        if let Some(mesh) = scene.mesh_mut("f22") {
+            mesh.transform.rotate(Vec3(1.0, 0.0, 0.0), 0.01);
+            //mesh.transform.scale += Vec3(0.001, 0.001, 0.001);
+            //mesh.transform.position += Vec3(0.01, 0.0, 0.0);
+        }
+
+        if eng.input().was_key_released(Key::Tab) {
+            flying = !flying;
+            println!("{}", if flying { "Fly camera" } else { "Orbit camera" });
+        }
+        // Isometric and dimetric views come from turning the camera to their angles
+        let projection_changed = eng.input().was_key_released(Key::P);
+        if projection_changed {
+            projection_type = match projection_type {
+                ProjectionType::Perspective => ProjectionType::Orthographic,
+                ProjectionType::Orthographic => ProjectionType::Isometric,
+                ProjectionType::Isometric => ProjectionType::Dimetric,
+                ProjectionType::Dimetric => ProjectionType::Perspective,
+            };
+            println!("{:?} projection", projection_type);
+            if let Some((yaw, pitch)) = projection_type.axonometric_angles() {
+                orbit_controller.set_angles(yaw, pitch);
+            }
+        }
+
+        let controller: &mut dyn CameraController = if flying {
+            &mut fly_controller
+        } else {
+            &mut orbit_controller
+        };
+
+        if let Some(camera) = scene.active_camera_mut() {
+            if projection_changed {
+                camera.set_projection(projection_for(projection_type));
+                if let Some((yaw, pitch)) = projection_type.axonometric_angles() {
+                    camera.set_orientation(yaw, pitch, 0.0);
                 }
             }
-        });
-    });
-}
+            eng.control_camera(controller, camera);
+        }
 
-fn draw_filled_triangle(a: Vec2, b: Vec2, c: Vec2, color: u32, eng: &mut EngineCore) {
-    let mut points = vec![a, b, c];
-    points.sort_by(|a, b| a.y().partial_cmp(&b.y()).unwrap());
-    //println!("points {:?}", points);
-
-    let a = points[0];
-    let b = points[1];
-    let c = points[2];
-
-    if b.y() == c.y() {
-        draw_flat_bottom(a, b, c, color, eng);
-    } else if a.y() == b.y() {
-        draw_flat_top(a, b, c, color, eng);
-    } else {
-        let my = b.y();
-        let mx = ((c.x() - a.x()) * (b.y() - a.y())) / (c.y() - a.y()) + a.x();
-
-        draw_flat_bottom(a, b, Vec2(mx, my), color, eng);
-        draw_flat_top(b, Vec2(mx, my), c, color, eng);
-    }
-}
-
-fn draw_flat_top(a: Vec2, b: Vec2, c: Vec2, color: u32, eng: &mut EngineCore) {
-    let start = c;
-
-    let end1 = a;
-    let end2 = b;
-
-    let s1 = (end1.x() - start.x()) / (end1.y() - start.y());
-    let s2 = (end2.x() - start.x()) / (end2.y() - start.y());
-
-    let y1 = end1.y().round() as usize;
-    let y2 = start.y().round() as usize;
-
-    let mut x1 = end1.x();
-    let mut x2 = end2.x();
-
-    for y in y1..y2 {
-        eng.draw_line(x1, y as f64, x2, y as f64, color);
-        x1 += s1;
-        x2 += s2;
-    }
-}
-
-fn draw_flat_bottom(a: Vec2, b: Vec2, c: Vec2, color: u32, eng: &mut EngineCore) {
-    let start = a;
-
-    let end1 = b;
-    let end2 = c;
-
-    let s1 = (end1.x() - start.x()) / (end1.y() - start.y());
-    let s2 = (end2.x() - start.x()) / (end2.y() - start.y());
-
-    let y1 = start.y().round() as usize;
-    let y2 = end1.y().round() as usize;
-
-    let mut x1 = start.x();
-    let mut x2 = start.x();
-
-    //println!("draw from {} to {}", y1, y2);
-    for y in y1..y2 {
-        //println!("draw flat bottom x1{} x2{} y{}", x1, x2, y);
-        eng.draw_line(x1, y as f64, x2, y as f64, color);
-        x1 += s1;
-        x2 += s2;
-    }
+        eng.render_scene(&scene);
+    });
 }
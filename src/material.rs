@@ -0,0 +1,172 @@
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+use vecx::{Vec3, VecX};
+
+use crate::obj::ObjError;
+use crate::texture::Texture;
+
+/// Surface description read from a Wavefront MTL file. Textures are shared by the
+/// materials and meshes using them
+pub struct Material {
+    pub name: String,
+    /// Ka
+    pub ambient: Vec3,
+    /// Kd
+    pub diffuse: Vec3,
+    /// Ks
+    pub specular: Vec3,
+    /// Ns
+    pub shininess: f64,
+    /// d, or 1 - Tr
+    pub opacity: f64,
+    /// map_Kd
+    pub diffuse_texture: Option<Arc<Texture>>,
+    /// map_Bump
+    pub bump_texture: Option<Arc<Texture>>,
+}
+
+impl Material {
+    pub fn new(name: &str) -> Self {
+        Material {
+            name: name.to_string(),
+            ambient: Vec3(1.0, 1.0, 1.0),
+            diffuse: Vec3(1.0, 1.0, 1.0),
+            specular: Vec3(0.0, 0.0, 0.0),
+            shininess: 0.0,
+            opacity: 1.0,
+            diffuse_texture: None,
+            bump_texture: None,
+        }
+    }
+
+    /// Diffuse color and opacity packed as ARGB
+    pub fn color(&self) -> u32 {
+        let channel = |value: f64| (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+        u32::from_be_bytes([
+            channel(self.opacity),
+            channel(self.diffuse.x()),
+            channel(self.diffuse.y()),
+            channel(self.diffuse.z()),
+        ])
+    }
+
+    /// Reads the file, see `parse_mtl`
+    pub fn load_mtl(path: &str) -> Result<(Vec<Material>, Vec<ObjError>), ObjError> {
+        let contents = fs::read_to_string(path).map_err(|e| ObjError::read(path, e))?;
+        Ok(Self::parse_mtl(&contents, path))
+    }
+
+    /// Parses every material of the MTL file at `path`, texture paths are relative to
+    /// it. Malformed statements and textures that fail to load are skipped and returned
+    /// as warnings on their line
+    pub fn parse_mtl(contents: &str, path: &str) -> (Vec<Material>, Vec<ObjError>) {
+        let directory = Path::new(path).parent().unwrap_or(Path::new(""));
+
+        let mut materials: Vec<Material> = Vec::new();
+        let mut warnings = Vec::new();
+
+        for (index, text) in contents.lines().enumerate() {
+            let mut tokens = text.split_whitespace();
+            let keyword = match tokens.next() {
+                Some(keyword) if !keyword.starts_with('#') => keyword,
+                _ => continue,
+            };
+            let arguments: Vec<&str> = tokens.collect();
+
+            let result = match (keyword, materials.last_mut()) {
+                ("newmtl", _) => {
+                    materials.push(Material::new(&arguments.join(" ")));
+                    Ok(())
+                }
+                (_, None) => Err(format!("{} before the first newmtl", keyword)),
+                (_, Some(material)) => match keyword {
+                    "Ka" => parse_color(&arguments).map(|color| material.ambient = color),
+                    "Kd" => parse_color(&arguments).map(|color| material.diffuse = color),
+                    "Ks" => parse_color(&arguments).map(|color| material.specular = color),
+                    "Ns" => parse_float(&arguments).map(|value| material.shininess = value),
+                    "d" => parse_float(&arguments).map(|value| material.opacity = value),
+                    "Tr" => parse_float(&arguments).map(|value| material.opacity = 1.0 - value),
+                    "map_Kd" => load_texture(directory, &arguments)
+                        .map(|texture| material.diffuse_texture = Some(texture)),
+                    "map_Bump" | "map_bump" | "bump" => load_texture(directory, &arguments)
+                        .map(|texture| material.bump_texture = Some(texture)),
+                    _ => Ok(()),
+                },
+            };
+
+            if let Err(reason) = result {
+                warnings.push(ObjError::on_line(path, index + 1, reason));
+            }
+        }
+
+        (materials, warnings)
+    }
+}
+
+fn parse_number(value: &str) -> Result<f64, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid number '{}'", value))
+}
+
+fn parse_float(arguments: &[&str]) -> Result<f64, String> {
+    let value = arguments.first().ok_or("missing value")?;
+    parse_number(value)
+}
+
+/// `r g b`, a single value is used for all three channels
+fn parse_color(arguments: &[&str]) -> Result<Vec3, String> {
+    let r = parse_float(arguments)?;
+    let g = arguments.get(1).map_or(Ok(r), |g| parse_number(g))?;
+    let b = arguments.get(2).map_or(Ok(r), |b| parse_number(b))?;
+    Ok(Vec3(r, g, b))
+}
+
+/// Texture maps may be preceded by options (`-bm 1.0 file.png`), the file name comes last
+fn load_texture(directory: &Path, arguments: &[&str]) -> Result<Arc<Texture>, String> {
+    let file_name = arguments.last().ok_or("missing texture file name")?;
+    let path = directory.join(file_name);
+    let path = path.to_string_lossy();
+
+    match Texture::load(&path) {
+        Ok(texture) => Ok(Arc::new(texture)),
+        Err(e) => Err(format!("failed to load texture {}: {}", path, e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn malformed_statements_are_warnings_on_their_line() {
+        let (materials, warnings) = Material::parse_mtl(
+            "Kd 1 1 1\nnewmtl red\nKd 1 0 x\nKs\nNs 8\nd 0.5\n",
+            "test.mtl",
+        );
+
+        assert_eq!(materials.len(), 1);
+        assert_eq!(materials[0].diffuse, Vec3(1.0, 1.0, 1.0));
+        assert_eq!(materials[0].shininess, 8.0);
+        assert_eq!(materials[0].opacity, 0.5);
+
+        let warnings: Vec<String> = warnings.iter().map(|w| w.to_string()).collect();
+        assert_eq!(
+            warnings,
+            [
+                "test.mtl:1: Kd before the first newmtl",
+                "test.mtl:3: invalid number 'x'",
+                "test.mtl:4: missing value",
+            ]
+        );
+    }
+
+    #[test]
+    fn single_values_are_used_for_every_channel() {
+        let (materials, warnings) = Material::parse_mtl("newmtl grey\nKa 0.5\n", "test.mtl");
+        assert!(warnings.is_empty());
+        assert_eq!(materials[0].ambient, Vec3(0.5, 0.5, 0.5));
+    }
+}
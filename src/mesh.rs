@@ -1,9 +1,17 @@
+use std::collections::HashMap;
 use std::error::Error;
-use std::{fs, vec};
+use std::fmt;
+use std::sync::Arc;
+use std::vec;
 
 use ::vecx::{Vec3, VecX};
-use vecx::Matrix;
+use vecx::{Matrix, Vec2, Vec4};
 
+use crate::clipping::{clip_polygon, clip_segment, ClipVertex};
+use crate::material::Material;
+use crate::obj::{ObjError, ObjModel};
+use crate::quaternion::{EulerOrder, Quaternion};
+use crate::texture::Texture;
 use crate::Camera;
 
 const CUBE_VERTS: [Vec3; 8] = [
@@ -17,29 +25,114 @@ const CUBE_VERTS: [Vec3; 8] = [
     Vec3(-1.0, -1.0, 1.0),  // 8
 ];
 
+/// Corners of a cube face, in the order its quad's points are listed in `CUBE_FACES`
+const CUBE_UVS: [Vec2; 4] = [
+    Vec2(0.0, 0.0), // 1
+    Vec2(0.0, 1.0), // 2
+    Vec2(1.0, 1.0), // 3
+    Vec2(1.0, 0.0), // 4
+];
+
+const CUBE_FIRST_HALF: FaceAttributes = FaceAttributes {
+    uvs: Some([1, 2, 3]),
+    normals: None,
+    colors: None,
+    material: None,
+    polygon_edges: [true, true, false],
+    smoothing_group: None,
+};
+
+const CUBE_SECOND_HALF: FaceAttributes = FaceAttributes {
+    uvs: Some([1, 3, 4]),
+    normals: None,
+    colors: None,
+    material: None,
+    polygon_edges: [false, true, true],
+    smoothing_group: None,
+};
+
 const CUBE_FACES: [Face; 6 * 2] = [
     // front
-    Face(1, 2, 3, 0xFFFF0000),
-    Face(1, 3, 4, 0xFFFF0000),
+    Face(1, 2, 3, 0xFFFF0000, CUBE_FIRST_HALF),
+    Face(1, 3, 4, 0xFFFF0000, CUBE_SECOND_HALF),
     // right
-    Face(4, 3, 5, 0xFF00FF00),
-    Face(4, 5, 6, 0xFF00FF00),
+    Face(4, 3, 5, 0xFF00FF00, CUBE_FIRST_HALF),
+    Face(4, 5, 6, 0xFF00FF00, CUBE_SECOND_HALF),
     // back
-    Face(6, 5, 7, 0xFF0000FF),
-    Face(6, 7, 8, 0xFF0000FF),
+    Face(6, 5, 7, 0xFF0000FF, CUBE_FIRST_HALF),
+    Face(6, 7, 8, 0xFF0000FF, CUBE_SECOND_HALF),
     // left
-    Face(8, 7, 2, 0xFFFFFF00),
-    Face(8, 2, 1, 0xFFFFFF00),
+    Face(8, 7, 2, 0xFFFFFF00, CUBE_FIRST_HALF),
+    Face(8, 2, 1, 0xFFFFFF00, CUBE_SECOND_HALF),
     // top
-    Face(2, 7, 5, 0xFF00FFFF),
-    Face(2, 5, 3, 0xFF00FFFF),
+    Face(2, 7, 5, 0xFF00FFFF, CUBE_FIRST_HALF),
+    Face(2, 5, 3, 0xFF00FFFF, CUBE_SECOND_HALF),
     // bottom
-    Face(6, 8, 1, 0xFFFF00FF),
-    Face(6, 1, 4, 0xFFFF00FF),
+    Face(6, 8, 1, 0xFFFF00FF, CUBE_FIRST_HALF),
+    Face(6, 1, 4, 0xFFFF00FF, CUBE_SECOND_HALF),
 ];
 
+/// 1-based indices into the mesh's per-vertex attribute lists, in the same order as the face's points
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FaceAttributes {
+    pub uvs: Option<[usize; 3]>,
+    pub normals: Option<[usize; 3]>,
+    /// Vertex colors tinting the face's color
+    pub colors: Option<[usize; 3]>,
+    /// Index into the mesh's materials
+    pub material: Option<usize>,
+    /// Whether the edges a-b, b-c and c-a are on the outline of the polygon the face was
+    /// triangulated from, wireframes leave out the others
+    pub polygon_edges: [bool; 3],
+    /// Faces of the same group share their vertex normals, see
+    /// `Mesh::compute_smoothing_group_normals`
+    pub smoothing_group: Option<u32>,
+}
+
+impl FaceAttributes {
+    pub const NONE: FaceAttributes = FaceAttributes {
+        uvs: None,
+        normals: None,
+        colors: None,
+        material: None,
+        polygon_edges: [true; 3],
+        smoothing_group: None,
+    };
+}
+
+impl Default for FaceAttributes {
+    fn default() -> Self {
+        Self::NONE
+    }
+}
+
+/// Face referencing a vertex, uv or normal its mesh doesn't have
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FaceIndexError {
+    /// Position of the face in the mesh's faces
+    pub face: usize,
+    /// `vertex`, `uv`, `normal` or `color`
+    pub kind: &'static str,
+    /// 1-based, as stored in the face
+    pub index: usize,
+    /// Length of the list the index points into
+    pub count: usize,
+}
+
+impl fmt::Display for FaceIndexError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "face {} references {} {} but the mesh has {}",
+            self.face, self.kind, self.index, self.count
+        )
+    }
+}
+
+impl Error for FaceIndexError {}
+
 #[derive(Debug, Clone, Copy)]
-pub struct Face(pub usize, pub usize, pub usize, pub u32);
+pub struct Face(pub usize, pub usize, pub usize, pub u32, pub FaceAttributes);
 
 impl Face {
     pub fn color(&self) -> u32 {
@@ -49,12 +142,156 @@ impl Face {
     pub fn set_color(&mut self, color: u32) {
         self.3 = color;
     }
+
+    pub fn attributes(&self) -> &FaceAttributes {
+        &self.4
+    }
+}
+
+/// Values attached to each point of a triangle, interpolated across its surface
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VertexAttributes {
+    /// World space position, see `Triangle::with_world_positions`
+    pub position: Vec3,
+    pub uv: Vec2,
+    pub normal: Vec3,
+    /// Light received by the vertex, per color channel
+    pub shade: Vec3,
+    /// RGBA from 0 to 1 multiplying the triangle's color, see `Mesh::colors`
+    pub color: Vec4,
+    /// Reciprocal of the clip space w once projected, weighs perspective correct
+    /// interpolation. Always 1 with orthographic projections
+    pub inverse_w: f64,
+}
+
+impl VertexAttributes {
+    /// Attributes at `t` along the segment from `self` to `other`
+    pub fn lerp(&self, other: &Self, t: f64) -> Self {
+        VertexAttributes {
+            position: lerp_vec3(self.position, other.position, t),
+            uv: Vec2(
+                self.uv.x() + (other.uv.x() - self.uv.x()) * t,
+                self.uv.y() + (other.uv.y() - self.uv.y()) * t,
+            ),
+            normal: lerp_vec3(self.normal, other.normal, t),
+            shade: lerp_vec3(self.shade, other.shade, t),
+            color: lerp_vec4(self.color, other.color, t),
+            inverse_w: self.inverse_w + (other.inverse_w - self.inverse_w) * t,
+        }
+    }
+
+    /// Weighted sum of three vertices' attributes
+    pub fn weighted(attributes: &[VertexAttributes; 3], weights: [f64; 3]) -> Self {
+        let [a, b, c] = attributes;
+        let [wa, wb, wc] = weights;
+        VertexAttributes {
+            position: weighted_vec3([a.position, b.position, c.position], weights),
+            uv: Vec2(
+                a.uv.x() * wa + b.uv.x() * wb + c.uv.x() * wc,
+                a.uv.y() * wa + b.uv.y() * wb + c.uv.y() * wc,
+            ),
+            normal: weighted_vec3([a.normal, b.normal, c.normal], weights),
+            shade: weighted_vec3([a.shade, b.shade, c.shade], weights),
+            color: weighted_vec4([a.color, b.color, c.color], weights),
+            inverse_w: a.inverse_w * wa + b.inverse_w * wb + c.inverse_w * wc,
+        }
+    }
+}
+
+impl Default for VertexAttributes {
+    fn default() -> Self {
+        VertexAttributes {
+            position: Vec3(0.0, 0.0, 0.0),
+            uv: Vec2(0.0, 0.0),
+            normal: Vec3(0.0, 0.0, 0.0),
+            shade: Vec3(1.0, 1.0, 1.0),
+            color: Vec4(1.0, 1.0, 1.0, 1.0),
+            inverse_w: 1.0,
+        }
+    }
+}
+
+fn lerp_vec3(a: Vec3, b: Vec3, t: f64) -> Vec3 {
+    Vec3(
+        a.x() + (b.x() - a.x()) * t,
+        a.y() + (b.y() - a.y()) * t,
+        a.z() + (b.z() - a.z()) * t,
+    )
+}
+
+fn lerp_vec4(a: Vec4, b: Vec4, t: f64) -> Vec4 {
+    Vec4(
+        a.x() + (b.x() - a.x()) * t,
+        a.y() + (b.y() - a.y()) * t,
+        a.z() + (b.z() - a.z()) * t,
+        a.w() + (b.w() - a.w()) * t,
+    )
+}
+
+fn weighted_vec3(values: [Vec3; 3], weights: [f64; 3]) -> Vec3 {
+    let [a, b, c] = values;
+    let [wa, wb, wc] = weights;
+    Vec3(
+        a.x() * wa + b.x() * wb + c.x() * wc,
+        a.y() * wa + b.y() * wb + c.y() * wc,
+        a.z() * wa + b.z() * wb + c.z() * wc,
+    )
+}
+
+fn weighted_vec4(values: [Vec4; 3], weights: [f64; 3]) -> Vec4 {
+    let [a, b, c] = values;
+    let [wa, wb, wc] = weights;
+    Vec4(
+        a.x() * wa + b.x() * wb + c.x() * wc,
+        a.y() * wa + b.y() * wb + c.y() * wc,
+        a.z() * wa + b.z() * wb + c.z() * wc,
+        a.w() * wa + b.w() * wb + c.w() * wc,
+    )
+}
+
+/// Unit length vector, zero vectors stay zero
+pub(crate) fn normalized_or_zero(v: Vec3) -> Vec3 {
+    if v == Vec3::ZERO {
+        return v;
+    }
+
+    v.normalized()
+}
+
+/// Items at three 1-based indices, None when one is out of range
+fn indexed<T: Copy>(list: &[T], indices: [usize; 3]) -> Option<[T; 3]> {
+    let [a, b, c] = indices.map(|index| index.checked_sub(1).and_then(|i| list.get(i)));
+    Some([*a?, *b?, *c?])
+}
+
+/// A clip space point along with the attributes to interpolate when clipping
+impl ClipVertex for (Vec4, VertexAttributes) {
+    fn position(&self) -> Vec4 {
+        self.0
+    }
+
+    fn lerp(&self, other: &Self, t: f64) -> Self {
+        (self.0.lerp(&other.0, t), self.1.lerp(&other.1, t))
+    }
 }
 
 #[derive(Debug, Clone, Copy)]
-pub struct Triangle(pub Vec3, pub Vec3, pub Vec3, pub u32);
+pub struct Triangle(
+    pub Vec3,
+    pub Vec3,
+    pub Vec3,
+    pub u32,
+    pub [VertexAttributes; 3],
+    /// Index of the material in the mesh this triangle comes from
+    pub Option<usize>,
+);
 
 impl Triangle {
+    /// Triangle without any texture coordinates
+    pub fn new(a: Vec3, b: Vec3, c: Vec3, color: u32) -> Self {
+        Triangle(a, b, c, color, [VertexAttributes::default(); 3], None)
+    }
+
     /// Returns the first point of this triangle
     pub fn a(&self) -> Vec3 {
         self.0
@@ -78,55 +315,165 @@ impl Triangle {
         self.3 = color;
     }
 
+    pub fn attributes(&self) -> &[VertexAttributes; 3] {
+        &self.4
+    }
+
+    pub fn material(&self) -> Option<usize> {
+        self.5
+    }
+
+    /// Returns the normals of the three points
+    pub fn normals(&self) -> [Vec3; 3] {
+        [self.4[0].normal, self.4[1].normal, self.4[2].normal]
+    }
+
+    /// Copies the current points into the attributes' positions, called right after the
+    /// world transform so positions can be interpolated for per-pixel lighting
+    pub fn with_world_positions(&self) -> Triangle {
+        let mut triangle = *self;
+        for (attributes, point) in triangle.4.iter_mut().zip([self.0, self.1, self.2]) {
+            attributes.position = point;
+        }
+        triangle
+    }
+
+    /// Attributes with each normal replaced by `f(normal)`, renormalized
+    fn transformed_normals<F: Fn(Vec3) -> Vec3>(&self, f: F) -> [VertexAttributes; 3] {
+        let mut attributes = self.4;
+        for attribute in attributes.iter_mut() {
+            attribute.normal = normalized_or_zero(f(attribute.normal));
+        }
+        attributes
+    }
+
+    /// Returns the texture coordinates of the three points
+    pub fn uvs(&self) -> [Vec2; 3] {
+        [self.4[0].uv, self.4[1].uv, self.4[2].uv]
+    }
+
     pub fn avg_z(&self) -> f64 {
         (self.0.z() + self.1.z() + self.2.z()) / 3.0
     }
 
+    /// Projects the triangle to normalized device coordinates, z holds each point's depth
+    /// (see `Camera::depth`) which is what the depth buffer compares
     pub fn projected(&self, cam: &Camera) -> Triangle {
-        Triangle(
-            Vec3::from(cam.project(&self.a())),
-            Vec3::from(cam.project(&self.b())),
-            Vec3::from(cam.project(&self.c())),
-            self.color(),
+        let mut attributes = self.4;
+        let mut project = |i: usize, point: Vec3| {
+            let projected = cam.project(&point);
+            attributes[i].inverse_w = projected.w();
+            Vec3(projected.x(), projected.y(), cam.depth(&projected))
+        };
+
+        let (a, b, c) = (
+            project(0, self.a()),
+            project(1, self.b()),
+            project(2, self.c()),
+        );
+        Triangle(a, b, c, self.color(), attributes, self.5)
+    }
+
+    /// Each point's 1/w, see `VertexAttributes::inverse_w`
+    pub fn inverse_w(&self) -> [f64; 3] {
+        self.4.map(|attributes| attributes.inverse_w)
+    }
+
+    /// Clips the triangle against the camera's frustum in clip space before projecting it,
+    /// the resulting polygon is split back into triangles laid out like `projected` ones
+    pub fn clipped(&self, cam: &Camera) -> Vec<Triangle> {
+        self.clipped_from(
+            [
+                cam.project_clip(&self.a()),
+                cam.project_clip(&self.b()),
+                cam.project_clip(&self.c()),
+            ],
+            cam,
         )
     }
 
-    /// Only applies rotation and scale TODO: implement translation
+    /// Same as `clipped` with the points already projected to clip space
+    pub fn clipped_from(&self, clip_points: [Vec4; 3], cam: &Camera) -> Vec<Triangle> {
+        let polygon = clip_polygon(&[
+            (clip_points[0], self.4[0]),
+            (clip_points[1], self.4[1]),
+            (clip_points[2], self.4[2]),
+        ]);
+
+        let projected: Vec<(Vec3, VertexAttributes)> = polygon
+            .iter()
+            .map(|(point, attributes)| {
+                let projected = cam.normalize(*point);
+                let attributes = VertexAttributes {
+                    inverse_w: projected.w(),
+                    ..*attributes
+                };
+                let depth = cam.depth(&projected);
+                (Vec3(projected.x(), projected.y(), depth), attributes)
+            })
+            .collect();
+
+        (1..projected.len().saturating_sub(1))
+            .map(|i| {
+                let (a, a_attributes) = projected[0];
+                let (b, b_attributes) = projected[i];
+                let (c, c_attributes) = projected[i + 1];
+                Triangle(
+                    a,
+                    b,
+                    c,
+                    self.color(),
+                    [a_attributes, b_attributes, c_attributes],
+                    self.5,
+                )
+            })
+            .collect()
+    }
+
+    /// Same as `matrix_transform` without building any matrix, normals are not transformed
     pub fn transformed(&self, transform: &Transform) -> Self {
         let mut transformed_tri = self.scale(transform.scale);
-        transformed_tri = transformed_tri.rotate(transform.rotation);
+        let rotation = transform.rotation;
+        transformed_tri = Triangle(
+            rotation.rotate_vector(transformed_tri.0),
+            rotation.rotate_vector(transformed_tri.1),
+            rotation.rotate_vector(transformed_tri.2),
+            transformed_tri.3,
+            transformed_tri.4,
+            transformed_tri.5,
+        );
         transformed_tri = transformed_tri.translate(transform.position);
         transformed_tri
     }
 
     pub fn matrix_transform(&self, transform: &Transform) -> Self {
-        let scale_matrix = Matrix::m4_scale(transform.scale);
-        let rot_x_matrix = Matrix::m4_rotate_x(transform.rotation.x());
-        let rot_y_matrix = Matrix::m4_rotate_y(transform.rotation.y());
-        let rot_z_matrix = Matrix::m4_rotate_z(transform.rotation.z());
-        let translation_matrix = Matrix::m4_translate(transform.position);
-
-        //println!("Tri self {:?}", self);
-
-        //translation_mat * rot_z_mat * rot_y_mat * rot_x_mat * scale_mat;
-        let mut world_matrix: Matrix = Matrix::id4();
-        world_matrix = scale_matrix * world_matrix;
-        world_matrix = rot_z_matrix * world_matrix;
-        world_matrix = rot_y_matrix * world_matrix;
-        world_matrix = rot_x_matrix * world_matrix;
-        world_matrix = translation_matrix * world_matrix;
+        self.world_transform(&transform.matrix(), &transform.normal_matrix())
+    }
 
-        let transformed = Triangle(
-            Vec3::from(&world_matrix * &self.0.as_mat4(1.0)),
-            Vec3::from(&world_matrix * &self.1.as_mat4(1.0)),
-            Vec3::from(&world_matrix * &self.2.as_mat4(1.0)),
+    /// Transforms the points by the world matrix and the normals by the matching normal
+    /// matrix, see `Transform::normal_matrix`
+    pub fn world_transform(&self, world_matrix: &Matrix, normal_matrix: &Matrix) -> Self {
+        Triangle(
+            Vec3::from(world_matrix * &self.0.as_mat4(1.0)),
+            Vec3::from(world_matrix * &self.1.as_mat4(1.0)),
+            Vec3::from(world_matrix * &self.2.as_mat4(1.0)),
             self.color(),
-        );
-
-        //let transformed = Triangle(self.0, self.1, self.2, self.color());
+            self.transformed_normals(|normal| Vec3::from(normal_matrix * &normal.as_mat4(0.0))),
+            self.5,
+        )
+    }
 
-        //println!("Tri outT {:?}", transformed);
-        transformed
+    /// Moves the points to the camera's space, attributes stay in world space for lighting
+    pub fn view_transform(&self, cam: &Camera) -> Triangle {
+        let view_matrix = cam.view_matrix();
+        Triangle(
+            Vec3::from(&view_matrix * &self.0.as_mat4(1.0)),
+            Vec3::from(&view_matrix * &self.1.as_mat4(1.0)),
+            Vec3::from(&view_matrix * &self.2.as_mat4(1.0)),
+            self.color(),
+            self.4,
+            self.5,
+        )
     }
 
     pub fn translate(&self, translation: Vec3) -> Triangle {
@@ -135,11 +482,20 @@ impl Triangle {
             self.1 + translation,
             self.2 + translation,
             self.color(),
+            self.4,
+            self.5,
         )
     }
 
     pub fn scale(&self, scale: Vec3) -> Triangle {
-        Triangle(self.0 * scale, self.1 * scale, self.2 * scale, self.color())
+        Triangle(
+            self.0 * scale,
+            self.1 * scale,
+            self.2 * scale,
+            self.color(),
+            self.4,
+            self.5,
+        )
     }
 
     pub fn rotate(&self, rotation: Vec3) -> Triangle {
@@ -148,6 +504,8 @@ impl Triangle {
             self.1.rot(&rotation),
             self.2.rot(&rotation),
             self.color(),
+            self.transformed_normals(|normal| normal.rot(&rotation)),
+            self.5,
         )
     }
 
@@ -172,6 +530,17 @@ impl Triangle {
 
         return normal.dot(&tri_to_viewer) < 0.0;
     }
+
+    /// `should_cull` for a viewer infinitely far away looking along `view_direction`, as
+    /// with orthographic projections
+    pub fn should_cull_towards(&self, view_direction: Vec3) -> bool {
+        let normal = self.normal();
+        if normal == Vec3::ZERO {
+            return true;
+        }
+
+        normal.dot(&view_direction) > 0.0
+    }
 }
 
 impl IntoIterator for Triangle {
@@ -188,7 +557,7 @@ impl IntoIterator for Triangle {
 impl FromIterator<Vec3> for Triangle {
     fn from_iter<T: IntoIterator<Item = Vec3>>(iter: T) -> Self {
         let mut iterator = iter.into_iter();
-        Triangle(
+        Triangle::new(
             iterator.next().unwrap(),
             iterator.next().unwrap(),
             iterator.next().unwrap(),
@@ -216,25 +585,88 @@ impl Iterator for TriangleIter {
     }
 }
 
+#[derive(Clone, Copy)]
 pub struct Transform {
     pub position: Vec3,
-    pub rotation: Vec3,
+    pub rotation: Quaternion,
     pub scale: Vec3,
 }
 
+impl Transform {
+    /// Replaces the rotation by Euler angles, `EulerOrder::ZYX` matches the fixed order
+    /// Euler rotations used to be applied in
+    pub fn set_euler(&mut self, angles: Vec3, order: EulerOrder) {
+        self.rotation = Quaternion::from_euler(angles, order);
+    }
+
+    pub fn euler(&self, order: EulerOrder) -> Vec3 {
+        self.rotation.to_euler(order)
+    }
+
+    /// Rotates around an axis of the parent space, after the current rotation
+    pub fn rotate(&mut self, axis: Vec3, angle: f64) {
+        self.rotation = (Quaternion::from_axis_angle(axis, angle) * self.rotation).normalized();
+    }
+
+    /// Rotates around an axis of the object's own space, before the current rotation
+    pub fn rotate_local(&mut self, axis: Vec3, angle: f64) {
+        self.rotation = (self.rotation * Quaternion::from_axis_angle(axis, angle)).normalized();
+    }
+
+    /// Scales, then rotates, then translates
+    pub fn matrix(&self) -> Matrix {
+        let scale_matrix = Matrix::m4_scale(self.scale);
+        let rotation_matrix = self.rotation.to_matrix();
+        let translation_matrix = Matrix::m4_translate(self.position);
+
+        //translation_mat * rotation_mat * scale_mat;
+        let mut world_matrix: Matrix = Matrix::id4();
+        world_matrix = scale_matrix * world_matrix;
+        world_matrix = rotation_matrix * world_matrix;
+        world_matrix = translation_matrix * world_matrix;
+        world_matrix
+    }
+
+    /// Normals follow the rotation but the inverse of the scale, keeping them
+    /// perpendicular to non-uniformly scaled surfaces. Normal matrices of nested
+    /// transforms multiply like their matrices do
+    pub fn normal_matrix(&self) -> Matrix {
+        let scale = self.scale;
+        let mut normal_matrix: Matrix = Matrix::id4();
+        normal_matrix = Matrix::m4_scale(Vec3(1.0 / scale.x(), 1.0 / scale.y(), 1.0 / scale.z()))
+            * normal_matrix;
+        normal_matrix = self.rotation.to_matrix() * normal_matrix;
+        normal_matrix
+    }
+}
+
 impl Default for Transform {
     fn default() -> Self {
         Transform {
             position: Vec3::zero(),
-            rotation: Vec3::zero(),
+            rotation: Quaternion::IDENTITY,
             scale: Vec3::one(),
         }
     }
 }
 
+/// Cloning copies the geometry, materials and textures stay shared
+#[derive(Clone)]
 pub struct Mesh {
     pub vertices: Vec<Vec3>,
     pub faces: Vec<Face>,
+    /// Texture coordinates referenced by the faces' attributes
+    pub uvs: Vec<Vec2>,
+    /// Vertex normals referenced by the faces' attributes
+    pub normals: Vec<Vec3>,
+    /// Vertex colors referenced by the faces' attributes, RGBA from 0 to 1
+    pub colors: Vec<Vec4>,
+    /// Sampled by the textured render mode when a face's material has no texture, may be
+    /// shared with other meshes
+    pub texture: Option<Arc<Texture>>,
+    /// Materials referenced by the faces' attributes, shared with the other meshes loaded
+    /// from the same file
+    pub materials: Vec<Arc<Material>>,
     pub transform: Transform,
 }
 
@@ -252,113 +684,355 @@ impl<'a> MeshIter<'a> {
 impl<'a> Iterator for MeshIter<'a> {
     type Item = Triangle;
 
+    /// Faces with indices out of range are skipped
     fn next(&mut self) -> Option<Self::Item> {
-        if self.current < self.mesh.faces.len() {
+        while self.current < self.mesh.faces.len() {
             let face = self.mesh.faces[self.current];
-            let v1 = self.mesh.vertices[face.0 - 1];
-            let v2 = self.mesh.vertices[face.1 - 1];
-            let v3 = self.mesh.vertices[face.2 - 1];
-
             self.current += 1;
-            return Some(Triangle(v1, v2, v3, face.color()));
+
+            let [v1, v2, v3] = match indexed(&self.mesh.vertices, [face.0, face.1, face.2]) {
+                Some(vertices) => vertices,
+                None => continue,
+            };
+
+            let mut attributes = [VertexAttributes::default(); 3];
+            if let Some(uvs) = face.attributes().uvs {
+                let uvs = match indexed(&self.mesh.uvs, uvs) {
+                    Some(uvs) => uvs,
+                    None => continue,
+                };
+                for (attribute, uv) in attributes.iter_mut().zip(uvs) {
+                    attribute.uv = uv;
+                }
+            }
+            if let Some(colors) = face.attributes().colors {
+                let colors = match indexed(&self.mesh.colors, colors) {
+                    Some(colors) => colors,
+                    None => continue,
+                };
+                for (attribute, color) in attributes.iter_mut().zip(colors) {
+                    attribute.color = color;
+                }
+            }
+
+            let mut triangle = Triangle(
+                v1,
+                v2,
+                v3,
+                face.color(),
+                attributes,
+                face.attributes().material,
+            );
+
+            // Faces without vertex normals are shaded flat
+            let normals = match face.attributes().normals {
+                Some(normals) => match indexed(&self.mesh.normals, normals) {
+                    Some(normals) => normals,
+                    None => continue,
+                },
+                None => [triangle.normal(); 3],
+            };
+            for (attribute, normal) in triangle.4.iter_mut().zip(normals) {
+                attribute.normal = normal;
+            }
+
+            return Some(triangle);
         }
 
         None
     }
 }
 
+/// A mesh's vertices and normals transformed once for the frame, faces are then assembled
+/// by index instead of transforming the three corners of every triangle
+pub struct TransformedMesh<'a> {
+    mesh: &'a Mesh,
+    world: Vec<Vec3>,
+    view: Vec<Vec3>,
+    clip: Vec<Vec4>,
+    normals: Vec<Vec3>,
+}
+
+impl<'a> TransformedMesh<'a> {
+    /// `world_matrix` and `normal_matrix` place the mesh in the world, see
+    /// `Transform::normal_matrix`
+    pub fn new(
+        mesh: &'a Mesh,
+        world_matrix: &Matrix,
+        normal_matrix: &Matrix,
+        cam: &Camera,
+    ) -> Self {
+        let model_view = &cam.view_matrix() * world_matrix;
+        let model_view_projection = cam.projection().matrix() * &model_view;
+
+        let transform = |matrix: &Matrix, point: &Vec3| matrix * &point.as_mat4(1.0);
+
+        TransformedMesh {
+            mesh,
+            world: mesh
+                .vertices
+                .iter()
+                .map(|v| Vec3::from(transform(world_matrix, v)))
+                .collect(),
+            view: mesh
+                .vertices
+                .iter()
+                .map(|v| Vec3::from(transform(&model_view, v)))
+                .collect(),
+            clip: mesh
+                .vertices
+                .iter()
+                .map(|v| Vec4::from(transform(&model_view_projection, v)))
+                .collect(),
+            normals: mesh
+                .normals
+                .iter()
+                .map(|n| normalized_or_zero(Vec3::from(normal_matrix * &n.as_mat4(0.0))))
+                .collect(),
+        }
+    }
+
+    pub fn mesh(&self) -> &Mesh {
+        self.mesh
+    }
+
+    pub fn face_count(&self) -> usize {
+        self.mesh.faces.len()
+    }
+
+    /// The face with its points in camera space, its attributes carry world positions and
+    /// normals like `Triangle::with_world_positions` ones. None when the face has indices
+    /// out of range, see `Mesh::validate`
+    pub fn triangle(&self, face: usize) -> Option<Triangle> {
+        let face = self.mesh.faces[face];
+        let vertices = [face.0, face.1, face.2];
+        let world = indexed(&self.world, vertices)?;
+
+        let mut attributes = [VertexAttributes::default(); 3];
+        for (attribute, position) in attributes.iter_mut().zip(world) {
+            attribute.position = position;
+        }
+        if let Some(uvs) = face.attributes().uvs {
+            for (attribute, uv) in attributes.iter_mut().zip(indexed(&self.mesh.uvs, uvs)?) {
+                attribute.uv = uv;
+            }
+        }
+        if let Some(colors) = face.attributes().colors {
+            let colors = indexed(&self.mesh.colors, colors)?;
+            for (attribute, color) in attributes.iter_mut().zip(colors) {
+                attribute.color = color;
+            }
+        }
+
+        // Faces without vertex normals are shaded flat
+        let normals = match face.attributes().normals {
+            Some(normals) => indexed(&self.normals, normals)?,
+            None => {
+                let [a, b, c] = world;
+                [Triangle::new(a, b, c, 0).normal(); 3]
+            }
+        };
+        for (attribute, normal) in attributes.iter_mut().zip(normals) {
+            attribute.normal = normal;
+        }
+
+        let [a, b, c] = indexed(&self.view, vertices)?;
+        Some(Triangle(
+            a,
+            b,
+            c,
+            face.color(),
+            attributes,
+            face.attributes().material,
+        ))
+    }
+
+    /// The face's points in clip space, to pass to `Triangle::clipped_from`. None when a
+    /// vertex index is out of range
+    pub fn clip_points(&self, face: usize) -> Option<[Vec4; 3]> {
+        let face = self.mesh.faces[face];
+        indexed(&self.clip, [face.0, face.1, face.2])
+    }
+
+    /// Clip space segments of the face's polygon edges clipped to the view frustum
+    pub fn polygon_edges(&self, face: usize) -> Vec<(Vec4, Vec4)> {
+        let [a, b, c] = match self.clip_points(face) {
+            Some(points) => points,
+            None => return Vec::new(),
+        };
+        let edges = self.mesh.faces[face].attributes().polygon_edges;
+
+        [(a, b), (b, c), (c, a)]
+            .into_iter()
+            .zip(edges)
+            .filter(|(_, on_outline)| *on_outline)
+            .filter_map(|((from, to), _)| clip_segment(from, to))
+            .collect()
+    }
+}
+
 impl Mesh {
     pub fn cube() -> Self {
-        Self::new(&CUBE_VERTS, &CUBE_FACES)
+        let mut cube = Self::new(&CUBE_VERTS, &CUBE_FACES);
+        cube.uvs = CUBE_UVS.to_vec();
+        cube.compute_normals();
+        cube
     }
 
+    /// Faces are not checked, those with indices out of range are skipped when rendering.
+    /// See `try_new`
     pub fn new(vertices: &[Vec3], faces: &[Face]) -> Self {
         Mesh {
             vertices: vertices.iter().map(|v| v.clone()).collect(),
             faces: faces.iter().map(|t| t.clone()).collect(),
+            uvs: Vec::new(),
+            normals: Vec::new(),
+            colors: Vec::new(),
+            texture: None,
+            materials: Vec::new(),
             transform: Transform {
                 position: Vec3(0.0, 0.0, 0.0),
-                rotation: Vec3(0.0, 0.0, 0.0),
+                rotation: Quaternion::IDENTITY,
                 scale: Vec3(1.0, 1.0, 1.0),
             },
         }
     }
 
+    /// `new` rejecting faces with indices out of range, see `validate`
+    pub fn try_new(vertices: &[Vec3], faces: &[Face]) -> Result<Self, FaceIndexError> {
+        let mesh = Self::new(vertices, faces);
+        mesh.validate()?;
+        Ok(mesh)
+    }
+
+    /// Loads an OBJ file, read failures and malformed lines are reported as an `ObjError`.
+    /// Skipped lines are dropped, see `load_obj_with_warnings`
     pub fn load_obj(path: &str) -> Result<Self, Box<dyn Error>> {
-        let contents = fs::read_to_string(path)?;
-        let vertices: Vec<Vec3> = contents
-            .lines()
-            .filter(|line| line.contains("v ") && !line.contains("#"))
-            .map(|line| {
-                let coords: Vec3 = line
-                    .split_whitespace()
-                    .filter(|coord| !coord.contains("v"))
-                    .map(|coord| {
-                        let coord: f64 = coord.parse().unwrap();
-                        return coord;
-                    })
-                    .collect();
-
-                return Vec3::from(coords);
-            })
-            .collect();
+        Self::load_obj_with_warnings(path).map(|(mesh, _)| mesh)
+    }
 
-        let faces: Vec<Face> = contents
-            .lines()
-            .filter(|line| line.contains("f ") && !line.contains("#"))
-            .map(|line| line.replace("f ", ""))
-            .map(|line| {
-                let faces: Vec<String> = line
-                    .split_whitespace()
-                    .into_iter()
-                    .map(|face| {
-                        let index: Vec<String> =
-                            face.split("/").take(1).map(|i| String::from(i)).collect();
-                        return index[0].clone();
-                    })
-                    .collect();
-                return faces;
-            })
+    /// `load_obj` along with the lines that were skipped, unsupported keywords and missing
+    /// material libraries
+    pub fn load_obj_with_warnings(path: &str) -> Result<(Self, Vec<ObjError>), Box<dyn Error>> {
+        let mut obj = ObjModel::load(path)?;
+        let warnings = std::mem::take(&mut obj.warnings);
+        let mesh = obj.into_mesh();
+        mesh.validate()?;
+
+        Ok((mesh, warnings))
+    }
+
+    /// Checks that the faces' 1-based indices all point into the mesh's vertices, uvs,
+    /// normals and colors. Faces failing it are skipped when rendering, and by `compute_normals`
+    pub fn validate(&self) -> Result<(), FaceIndexError> {
+        for (position, face) in self.faces.iter().enumerate() {
+            let attributes = face.attributes();
+            let lists = [
+                (
+                    "vertex",
+                    Some([face.0, face.1, face.2]),
+                    self.vertices.len(),
+                ),
+                ("uv", attributes.uvs, self.uvs.len()),
+                ("normal", attributes.normals, self.normals.len()),
+                ("color", attributes.colors, self.colors.len()),
+            ];
+
+            for (kind, indices, count) in lists {
+                for index in indices.into_iter().flatten() {
+                    if index == 0 || index > count {
+                        return Err(FaceIndexError {
+                            face: position,
+                            kind,
+                            index,
+                            count,
+                        });
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Replaces the vertex normals by the average of the normals of the faces sharing
+    /// each vertex, smoothing the whole mesh
+    pub fn compute_normals(&mut self) {
+        let mut normals = vec![Vec3::zero(); self.vertices.len()];
+
+        for face in self.faces.iter() {
+            let indices = [face.0, face.1, face.2];
+            let [a, b, c] = match indexed(&self.vertices, indices) {
+                Some(points) => points,
+                None => continue,
+            };
+            let face_normal = Triangle::new(a, b, c, face.color()).normal();
+
+            for index in indices {
+                normals[index - 1] += face_normal;
+            }
+        }
+
+        self.normals = normals.into_iter().map(normalized_or_zero).collect();
+        for face in self.faces.iter_mut() {
+            // Normals share the vertices' indices, out of range ones stay so
+            face.4.normals = Some([face.0, face.1, face.2]);
+        }
+    }
+
+    /// Replaces the vertex normals by the average of the normals of the faces sharing each
+    /// vertex within the same smoothing group, faces outside any group are shaded flat
+    pub fn compute_smoothing_group_normals(&mut self) {
+        let face_normals: Vec<Option<Vec3>> = self
+            .faces
+            .iter()
             .map(|face| {
-                let face_indices: Vec<usize> = face
-                    .iter()
-                    .map(|index| {
-                        //println!("index {:?}", index);
-                        let index: usize = index.parse().unwrap();
-                        return index;
-                    })
-                    .collect();
-
-                return Face(
-                    face_indices[0],
-                    face_indices[1],
-                    face_indices[2],
-                    0xFFFFFFFF, //rand::random(),
-                );
+                let [a, b, c] = indexed(&self.vertices, [face.0, face.1, face.2])?;
+                Some(Triangle::new(a, b, c, face.color()).normal())
             })
             .collect();
 
-        /*faces = faces
-        .chunks(2)
-        .map(|faces| {
-            let color: u32 = rand::random();
-            let mut f1 = Face::from(faces[0]);
-            let mut f2 = Face::from(faces[1]);
-
-            f1.set_color(color);
-            f2.set_color(color);
-            return [f1, f2];
-        })
-        .flatten()
-        .collect();*/
-        //println!("vertices {:?}", vertices);
-        //println!("faces: {:?}", faces);
-
-        Ok(Mesh {
-            vertices,
-            faces,
-            transform: Transform::default(),
-        })
+        // A vertex gets one normal per smoothing group it is part of
+        let mut normals: Vec<Vec3> = Vec::new();
+        let mut group_normals: HashMap<(usize, u32), usize> = HashMap::new();
+        for (face, face_normal) in self.faces.iter_mut().zip(face_normals) {
+            let (group, face_normal) = match (face.4.smoothing_group, face_normal) {
+                (Some(group), Some(face_normal)) => (group, face_normal),
+                _ => {
+                    face.4.normals = None;
+                    continue;
+                }
+            };
+
+            let indices = [face.0, face.1, face.2].map(|vertex| {
+                *group_normals.entry((vertex, group)).or_insert_with(|| {
+                    normals.push(Vec3::zero());
+                    normals.len()
+                })
+            });
+            for index in indices {
+                normals[index - 1] += face_normal;
+            }
+            face.4.normals = Some(indices);
+        }
+
+        self.normals = normals.into_iter().map(normalized_or_zero).collect();
+    }
+
+    pub fn material_for(&self, triangle: &Triangle) -> Option<&Material> {
+        triangle
+            .material()
+            .and_then(|material| self.materials.get(material))
+            .map(Arc::as_ref)
+    }
+
+    /// Texture of the triangle's material, falling back to the mesh's own texture
+    pub fn texture_for(&self, triangle: &Triangle) -> Option<&Texture> {
+        triangle
+            .material()
+            .and_then(|material| self.materials.get(material))
+            .and_then(|material| material.diffuse_texture.as_deref())
+            .or(self.texture.as_deref())
     }
 
     pub fn triangles(&self) -> MeshIter {
@@ -369,3 +1043,146 @@ impl Mesh {
         &mut self.transform
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::CameraProjection;
+
+    const TRIANGLE: [Vec3; 3] = [
+        Vec3(0.0, 0.0, 0.0),
+        Vec3(1.0, 0.0, 0.0),
+        Vec3(0.0, 1.0, 0.0),
+    ];
+
+    fn face(v1: usize, v2: usize, v3: usize) -> Face {
+        Face(v1, v2, v3, 0xFFFFFFFF, FaceAttributes::NONE)
+    }
+
+    /// A valid face followed by faces with a bad vertex, uv and normal index
+    fn broken_mesh() -> Mesh {
+        let bad_uv = Face(
+            1,
+            2,
+            3,
+            0xFFFFFFFF,
+            FaceAttributes {
+                uvs: Some([1, 1, 1]),
+                ..FaceAttributes::NONE
+            },
+        );
+        let bad_normal = Face(
+            1,
+            2,
+            3,
+            0xFFFFFFFF,
+            FaceAttributes {
+                normals: Some([1, 2, 5]),
+                ..FaceAttributes::NONE
+            },
+        );
+        Mesh::new(
+            &TRIANGLE,
+            &[face(1, 2, 3), face(0, 1, 2), bad_uv, bad_normal],
+        )
+    }
+
+    #[test]
+    fn try_new_rejects_indices_out_of_range() {
+        assert!(Mesh::try_new(&TRIANGLE, &[face(1, 2, 3)]).is_ok());
+
+        let error = Mesh::try_new(&TRIANGLE, &[face(1, 2, 3), face(3, 4, 1)])
+            .err()
+            .unwrap();
+        assert_eq!(
+            error,
+            FaceIndexError {
+                face: 1,
+                kind: "vertex",
+                index: 4,
+                count: 3,
+            }
+        );
+        assert_eq!(
+            error.to_string(),
+            "face 1 references vertex 4 but the mesh has 3"
+        );
+
+        let error = Mesh::try_new(&TRIANGLE, &[face(0, 1, 2)]).err().unwrap();
+        assert_eq!((error.kind, error.index), ("vertex", 0));
+
+        let error = broken_mesh().validate().err().unwrap();
+        assert_eq!((error.face, error.kind), (1, "vertex"));
+    }
+
+    #[test]
+    fn faces_with_indices_out_of_range_are_skipped() {
+        let mesh = broken_mesh();
+        assert_eq!(mesh.triangles().count(), 1);
+
+        let camera = Camera::look_at(
+            Vec3(0.0, 0.0, -5.0),
+            Vec3(0.0, 0.0, 0.0),
+            Vec3(0.0, 1.0, 0.0),
+            CameraProjection::perspective(1.0, 1.0, 0.1, 100.0),
+        );
+        let transformed = TransformedMesh::new(&mesh, &Matrix::id4(), &Matrix::id4(), &camera);
+        assert!(transformed.triangle(0).is_some());
+        assert!(transformed.clip_points(0).is_some());
+        for face in 1..transformed.face_count() {
+            assert!(transformed.triangle(face).is_none());
+        }
+        assert!(transformed.clip_points(1).is_none());
+        assert!(transformed.polygon_edges(1).is_empty());
+    }
+
+    #[test]
+    fn triangles_straddling_the_near_plane_are_split_in_two() {
+        let camera = Camera::new(
+            Vec3(0.0, 0.0, 0.0),
+            CameraProjection::perspective(1.0, 1.0, 0.1, 100.0),
+        );
+        let mut triangle = Triangle::new(TRIANGLE[0], TRIANGLE[1], TRIANGLE[2], 0xFFFFFFFF);
+        for (attributes, u) in triangle.4.iter_mut().zip([0.0, 1.0, 2.0]) {
+            attributes.uv = Vec2(u, 0.0);
+        }
+
+        // The first point is behind the near plane
+        let clip_points = [
+            Vec4(0.0, 0.0, -1.0, 1.0),
+            Vec4(0.5, 0.0, 1.0, 2.0),
+            Vec4(0.0, 0.5, 0.5, 1.0),
+        ];
+        let clipped = triangle.clipped_from(clip_points, &camera);
+        assert_eq!(clipped.len(), 2);
+
+        // Fanned from the same point, sharing the diagonal
+        assert_eq!(clipped[0].a(), clipped[1].a());
+        assert_eq!(clipped[0].c(), clipped[1].b());
+        // Half way along the edge from the first point
+        let uvs: Vec<f64> = clipped
+            .iter()
+            .flat_map(|triangle| triangle.4.map(|attributes| attributes.uv.x()))
+            .collect();
+        assert!(uvs.contains(&0.5));
+        assert!(!uvs.contains(&0.0));
+    }
+
+    #[test]
+    fn normals_skip_faces_with_indices_out_of_range() {
+        let mut mesh = Mesh::new(&TRIANGLE, &[face(1, 2, 3), face(2, 3, 7)]);
+        mesh.compute_normals();
+        assert_eq!(mesh.normals.len(), 3);
+        for normal in mesh.normals.iter() {
+            assert_eq!(normal.z().abs(), 1.0);
+        }
+
+        for face in mesh.faces.iter_mut() {
+            face.4.smoothing_group = Some(1);
+        }
+        mesh.compute_smoothing_group_normals();
+        assert_eq!(mesh.normals.len(), 3);
+        assert_eq!(mesh.faces[0].4.normals, Some([1, 2, 3]));
+        assert_eq!(mesh.faces[1].4.normals, None);
+    }
+}
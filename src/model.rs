@@ -0,0 +1,118 @@
+use std::error::Error;
+use std::sync::Arc;
+
+use crate::mesh::Mesh;
+use crate::obj::{ObjError, ObjModel};
+use crate::texture::Texture;
+
+/// Piece of a model with its own mesh, so it can be hidden, recolored or moved on its own
+pub struct ModelPart {
+    /// The group's name, or the object's for faces outside any group
+    pub name: String,
+    /// Object the part belongs to, empty when the file declares none
+    pub object: String,
+    pub mesh: Mesh,
+}
+
+/// Meshes loaded from a single file, split by the objects and groups it declares
+pub struct Model {
+    parts: Vec<ModelPart>,
+    warnings: Vec<ObjError>,
+}
+
+impl Model {
+    pub fn new(parts: Vec<ModelPart>) -> Self {
+        Model {
+            parts,
+            warnings: Vec::new(),
+        }
+    }
+
+    /// Loads an OBJ file with a part per `o` object and `g` group, faces keep their `s`
+    /// smoothing group. Read failures and malformed lines are reported as an `ObjError`,
+    /// skipped lines are kept in `warnings`
+    pub fn load_obj(path: &str) -> Result<Self, Box<dyn Error>> {
+        let obj = ObjModel::load(path)?;
+
+        let mut parts = Vec::new();
+        for (part, mesh) in obj.part_meshes() {
+            mesh.validate()?;
+            parts.push(ModelPart {
+                name: part.group.clone().unwrap_or_else(|| part.object.clone()),
+                object: part.object.clone(),
+                mesh,
+            });
+        }
+
+        Ok(Model {
+            parts,
+            warnings: obj.warnings,
+        })
+    }
+
+    /// Lines skipped while loading the model, unsupported keywords and missing material
+    /// libraries
+    pub fn warnings(&self) -> &[ObjError] {
+        &self.warnings
+    }
+
+    pub fn parts(&self) -> &[ModelPart] {
+        &self.parts
+    }
+
+    pub fn parts_mut(&mut self) -> &mut [ModelPart] {
+        &mut self.parts
+    }
+
+    /// First part with that name
+    pub fn part(&self, name: &str) -> Option<&ModelPart> {
+        self.parts.iter().find(|part| part.name == name)
+    }
+
+    pub fn part_mut(&mut self, name: &str) -> Option<&mut ModelPart> {
+        self.parts.iter_mut().find(|part| part.name == name)
+    }
+
+    /// Shares the texture between every part's mesh, see `Mesh::texture`
+    pub fn set_texture(&mut self, texture: Texture) {
+        let texture = Arc::new(texture);
+        for part in self.parts.iter_mut() {
+            part.mesh.texture = Some(Arc::clone(&texture));
+        }
+    }
+
+    pub fn into_parts(self) -> Vec<ModelPart> {
+        self.parts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parts_share_the_model_texture() {
+        let obj = ObjModel::parse(
+            "v 0 0 0\nv 1 0 0\nv 0 1 0\no a\nf 1 2 3\no b\nf 3 2 1\n",
+            "test.obj",
+        )
+        .unwrap();
+        let parts = obj
+            .part_meshes()
+            .into_iter()
+            .map(|(part, mesh)| ModelPart {
+                name: part.object.clone(),
+                object: part.object.clone(),
+                mesh,
+            })
+            .collect();
+        let mut model = Model::new(parts);
+
+        model.set_texture(Texture::checkerboard(4, 2, 0xFFFFFFFF, 0xFF000000));
+        let [a, b] = model.parts() else {
+            panic!("expected two parts");
+        };
+        let (a, b) = (a.mesh.texture.as_ref(), b.mesh.texture.as_ref());
+        assert!(Arc::ptr_eq(a.unwrap(), b.unwrap()));
+    }
+}
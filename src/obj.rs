@@ -0,0 +1,864 @@
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use vecx::{Vec2, Vec3, VecX};
+
+use crate::material::Material;
+use crate::mesh::{normalized_or_zero, Face, FaceAttributes, Mesh, Transform};
+
+/// OBJ or MTL file that could not be read, or a malformed line of one or a line skipped
+/// while loading it
+#[derive(Debug, Clone)]
+pub enum ObjError {
+    Read {
+        path: PathBuf,
+        source: Arc<io::Error>,
+    },
+    Line {
+        path: PathBuf,
+        /// 1-based, the first of the lines joined by a continuation
+        line: usize,
+        reason: String,
+    },
+}
+
+impl ObjError {
+    /// Reading the file at `path` failed
+    pub(crate) fn read(path: &str, source: io::Error) -> Self {
+        ObjError::Read {
+            path: PathBuf::from(path),
+            source: Arc::new(source),
+        }
+    }
+
+    /// Error on the 1-based `line` of the file at `path`
+    pub(crate) fn on_line(path: &str, line: usize, reason: String) -> Self {
+        ObjError::Line {
+            path: PathBuf::from(path),
+            line,
+            reason,
+        }
+    }
+
+    /// File the error is about, an MTL file for its own lines
+    pub fn path(&self) -> &Path {
+        match self {
+            ObjError::Read { path, .. } | ObjError::Line { path, .. } => path,
+        }
+    }
+}
+
+impl fmt::Display for ObjError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ObjError::Read { path, source } => write!(f, "{}: {}", path.display(), source),
+            ObjError::Line { path, line, reason } => {
+                write!(f, "{}:{}: {}", path.display(), line, reason)
+            }
+        }
+    }
+}
+
+impl Error for ObjError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ObjError::Read { source, .. } => Some(source.as_ref()),
+            ObjError::Line { .. } => None,
+        }
+    }
+}
+
+/// Corner of a face, 1-based indices as written in the file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct ObjVertex {
+    pub vertex: usize,
+    pub uv: Option<usize>,
+    pub normal: Option<usize>,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct ObjFace {
+    pub vertices: Vec<ObjVertex>,
+    /// Index into the model's materials
+    pub material: Option<usize>,
+    /// Index into the model's parts
+    pub part: usize,
+    /// None when smoothing is off
+    pub smoothing_group: Option<u32>,
+}
+
+/// Object and group faces were declared in, by the last `o` and `g` lines before them
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct ObjPart {
+    /// Empty before the first `o` line
+    pub object: String,
+    pub group: Option<String>,
+}
+
+/// Everything read from an OBJ file and its material libraries
+#[derive(Default)]
+pub(crate) struct ObjModel {
+    pub vertices: Vec<Vec3>,
+    pub uvs: Vec<Vec2>,
+    pub normals: Vec<Vec3>,
+    pub faces: Vec<ObjFace>,
+    pub materials: Vec<Arc<Material>>,
+    pub parts: Vec<ObjPart>,
+    /// Lines that were skipped, material libraries' included. Unsupported keywords are
+    /// only reported once
+    pub warnings: Vec<ObjError>,
+}
+
+impl ObjModel {
+    /// Reads the file, see `parse`
+    pub fn load(path: &str) -> Result<Self, ObjError> {
+        let contents = fs::read_to_string(path).map_err(|e| ObjError::read(path, e))?;
+        Self::parse(&contents, path)
+    }
+
+    /// Parses the contents of the file at `path`. Material libraries are looked up next
+    /// to it, a missing one is a warning and leaves its materials' faces white
+    pub fn parse(contents: &str, path: &str) -> Result<Self, ObjError> {
+        let directory = Path::new(path).parent().unwrap_or(Path::new(""));
+
+        let mut model = ObjModel::default();
+        // Materials are referenced by name until every library is loaded
+        let mut material_names: Vec<String> = Vec::new();
+        let mut current_material: Option<usize> = None;
+        let mut current_part = ObjPart {
+            object: String::new(),
+            group: None,
+        };
+        let mut smoothing_group: Option<u32> = None;
+        let mut skipped: HashSet<String> = HashSet::new();
+
+        for (line, text) in logical_lines(contents) {
+            let error = |reason: String| ObjError::on_line(path, line, reason);
+
+            let mut tokens = text.split_whitespace();
+            let keyword = match tokens.next() {
+                Some(keyword) => keyword,
+                None => continue,
+            };
+            let arguments: Vec<&str> = tokens.collect();
+
+            match keyword {
+                "v" => {
+                    // Optional w and vertex colors are ignored
+                    let [x, y, z] = parse_floats(&arguments, 3).map_err(error)?;
+                    model.vertices.push(Vec3(x, y, z));
+                }
+                "vt" => {
+                    let [u, v] = parse_floats(&arguments, 1).map_err(error)?;
+                    model.uvs.push(Vec2(u, v));
+                }
+                "vn" => {
+                    let [x, y, z] = parse_floats(&arguments, 3).map_err(error)?;
+                    model.normals.push(Vec3(x, y, z));
+                }
+                "f" => {
+                    let counts = [model.vertices.len(), model.uvs.len(), model.normals.len()];
+                    let vertices = parse_face(&arguments, counts).map_err(error)?;
+
+                    // Parts are only made once they have faces
+                    let part = match model.parts.iter().position(|p| *p == current_part) {
+                        Some(part) => part,
+                        None => {
+                            model.parts.push(current_part.clone());
+                            model.parts.len() - 1
+                        }
+                    };
+                    model.faces.push(ObjFace {
+                        vertices,
+                        material: current_material,
+                        part,
+                        smoothing_group,
+                    });
+                }
+                "usemtl" => {
+                    let name = arguments.join(" ");
+                    if name.is_empty() {
+                        return Err(error("missing material name".to_string()));
+                    }
+
+                    current_material = match material_names.iter().position(|n| *n == name) {
+                        Some(index) => Some(index),
+                        None => {
+                            material_names.push(name);
+                            Some(material_names.len() - 1)
+                        }
+                    };
+                }
+                "mtllib" => {
+                    let library_path = directory.join(arguments.join(" "));
+                    let library_path = library_path.to_string_lossy();
+                    match Material::load_mtl(&library_path) {
+                        Ok((materials, warnings)) => {
+                            model.materials.extend(materials.into_iter().map(Arc::new));
+                            model.warnings.extend(warnings);
+                        }
+                        Err(e) => model
+                            .warnings
+                            .push(error(format!("failed to load material library {}", e))),
+                    }
+                }
+                "o" => {
+                    current_part = ObjPart {
+                        object: arguments.join(" "),
+                        group: None,
+                    };
+                }
+                "g" => {
+                    let group = arguments.join(" ");
+                    current_part.group = Some(group).filter(|group| !group.is_empty());
+                }
+                "s" => {
+                    smoothing_group =
+                        match arguments.first() {
+                            Some(&"off") | Some(&"0") => None,
+                            Some(group) => Some(group.parse().map_err(|_| {
+                                error(format!("invalid smoothing group '{}'", group))
+                            })?),
+                            None => return Err(error("missing smoothing group".to_string())),
+                        };
+                }
+                _ => {
+                    if skipped.insert(keyword.to_string()) {
+                        let reason = format!("skipping unsupported {} lines", keyword);
+                        model.warnings.push(error(reason));
+                    }
+                }
+            }
+        }
+
+        let materials: Vec<Option<usize>> = material_names
+            .iter()
+            .map(|name| model.materials.iter().position(|m| m.name == *name))
+            .collect();
+        for face in model.faces.iter_mut() {
+            face.material = face.material.and_then(|name| materials[name]);
+        }
+
+        Ok(model)
+    }
+
+    /// Whole model as a single mesh
+    pub fn into_mesh(self) -> Mesh {
+        let faces: Vec<&ObjFace> = self.faces.iter().collect();
+        self.mesh(&faces)
+    }
+
+    /// Mesh of each part with faces, in the order parts first appear
+    pub fn part_meshes(&self) -> Vec<(&ObjPart, Mesh)> {
+        self.parts
+            .iter()
+            .enumerate()
+            .map(|(index, part)| {
+                let faces: Vec<&ObjFace> = self
+                    .faces
+                    .iter()
+                    .filter(|face| face.part == index)
+                    .collect();
+                (part, self.mesh(&faces))
+            })
+            .collect()
+    }
+
+    /// Mesh of the faces triangulated (see `triangulate`) holding only the vertices, uvs,
+    /// normals and materials they use. Normals are computed when the faces have none
+    fn mesh(&self, faces: &[&ObjFace]) -> Mesh {
+        let mut vertices = Remap::default();
+        let mut uvs = Remap::default();
+        let mut normals = Remap::default();
+        let mut materials = Remap::default();
+
+        let faces: Vec<Face> = faces
+            .iter()
+            .flat_map(|face| {
+                let points: Vec<Vec3> = face
+                    .vertices
+                    .iter()
+                    .map(|corner| self.vertices[corner.vertex - 1])
+                    .collect();
+                triangulate(&points)
+                    .into_iter()
+                    .map(move |triangle| (face, triangle))
+            })
+            .map(|(face, triangle)| {
+                let count = face.vertices.len();
+                let [a, b, c] = triangle.map(|corner| face.vertices[corner]);
+
+                // Consecutive corners make an edge of the polygon, others a diagonal
+                let polygon_edges = [(0, 1), (1, 2), (2, 0)].map(|(from, to)| {
+                    let (from, to) = (triangle[from], triangle[to]);
+                    (from + 1) % count == to || (to + 1) % count == from
+                });
+
+                let face_uvs = match (a.uv, b.uv, c.uv) {
+                    (Some(a), Some(b), Some(c)) => Some([a, b, c].map(|uv| uvs.index(uv))),
+                    _ => None,
+                };
+                let face_normals = match (a.normal, b.normal, c.normal) {
+                    (Some(a), Some(b), Some(c)) => {
+                        Some([a, b, c].map(|normal| normals.index(normal)))
+                    }
+                    _ => None,
+                };
+                let color = face
+                    .material
+                    .map_or(0xFFFFFFFF, |m| self.materials[m].color());
+
+                Face(
+                    vertices.index(a.vertex),
+                    vertices.index(b.vertex),
+                    vertices.index(c.vertex),
+                    color,
+                    FaceAttributes {
+                        uvs: face_uvs,
+                        normals: face_normals,
+                        colors: None,
+                        // Materials are indexed from 0
+                        material: face.material.map(|m| materials.index(m + 1) - 1),
+                        polygon_edges,
+                        smoothing_group: face.smoothing_group,
+                    },
+                )
+            })
+            .collect();
+
+        let mut mesh = Mesh {
+            vertices: vertices.values(&self.vertices),
+            faces,
+            uvs: uvs.values(&self.uvs),
+            normals: normals
+                .values(&self.normals)
+                .into_iter()
+                .map(normalized_or_zero)
+                .collect(),
+            colors: Vec::new(),
+            texture: None,
+            materials: materials.values(&self.materials),
+            transform: Transform::default(),
+        };
+
+        if mesh.normals.is_empty() {
+            if mesh
+                .faces
+                .iter()
+                .any(|face| face.attributes().smoothing_group.is_some())
+            {
+                mesh.compute_smoothing_group_normals();
+            } else {
+                mesh.compute_normals();
+            }
+        }
+
+        mesh
+    }
+}
+
+/// Renumbers 1-based indices into a list to indices into the list of the elements used
+#[derive(Default)]
+struct Remap {
+    indices: HashMap<usize, usize>,
+    used: Vec<usize>,
+}
+
+impl Remap {
+    fn index(&mut self, index: usize) -> usize {
+        let used = &mut self.used;
+        *self.indices.entry(index).or_insert_with(|| {
+            used.push(index);
+            used.len()
+        })
+    }
+
+    fn values<T: Clone>(&self, list: &[T]) -> Vec<T> {
+        self.used
+            .iter()
+            .map(|index| list[index - 1].clone())
+            .collect()
+    }
+}
+
+/// Splits a polygon into triangles of indices into `points` wound like the polygon, a fan
+/// when it is convex and ear clipping otherwise
+fn triangulate(points: &[Vec3]) -> Vec<[usize; 3]> {
+    let fan = |corners: &[usize]| -> Vec<[usize; 3]> {
+        (1..corners.len() - 1)
+            .map(|i| [corners[0], corners[i], corners[i + 1]])
+            .collect()
+    };
+    let corners: Vec<usize> = (0..points.len()).collect();
+    if points.len() == 3 {
+        return fan(&corners);
+    }
+
+    // Newell's normal, the polygon is flattened along its largest axis
+    let mut normal = [0.0; 3];
+    for (i, current) in points.iter().enumerate() {
+        let next = points[(i + 1) % points.len()];
+        normal[0] += (current.y() - next.y()) * (current.z() + next.z());
+        normal[1] += (current.z() - next.z()) * (current.x() + next.x());
+        normal[2] += (current.x() - next.x()) * (current.y() + next.y());
+    }
+    let axis = (0..3)
+        .max_by(|a, b| normal[*a].abs().total_cmp(&normal[*b].abs()))
+        .unwrap_or(2);
+    if normal[axis] == 0.0 {
+        return fan(&corners);
+    }
+
+    // Flattened so the polygon winds counterclockwise
+    let flat: Vec<(f64, f64)> = points
+        .iter()
+        .map(|point| {
+            let (u, v) = match axis {
+                0 => (point.y(), point.z()),
+                1 => (point.z(), point.x()),
+                _ => (point.x(), point.y()),
+            };
+            if normal[axis] > 0.0 {
+                (u, v)
+            } else {
+                (v, u)
+            }
+        })
+        .collect();
+    let cross = |a: usize, b: usize, c: usize| {
+        let (a, b, c) = (flat[a], flat[b], flat[c]);
+        (b.0 - a.0) * (c.1 - a.1) - (b.1 - a.1) * (c.0 - a.0)
+    };
+
+    let count = points.len();
+    let convex = (0..count).all(|i| cross(i, (i + 1) % count, (i + 2) % count) >= 0.0);
+    if convex {
+        return fan(&corners);
+    }
+
+    let mut remaining = corners;
+    let mut triangles = Vec::with_capacity(count - 2);
+    while remaining.len() > 3 {
+        let len = remaining.len();
+        let ear = (0..len).find(|&i| {
+            let (previous, current, next) = (
+                remaining[(i + len - 1) % len],
+                remaining[i],
+                remaining[(i + 1) % len],
+            );
+            if cross(previous, current, next) <= 0.0 {
+                return false;
+            }
+
+            // No other corner may lie inside the ear
+            remaining.iter().all(|&other| {
+                [previous, current, next].contains(&other)
+                    || cross(previous, current, other) < 0.0
+                    || cross(current, next, other) < 0.0
+                    || cross(next, previous, other) < 0.0
+            })
+        });
+
+        match ear {
+            Some(i) => {
+                triangles.push([
+                    remaining[(i + len - 1) % len],
+                    remaining[i],
+                    remaining[(i + 1) % len],
+                ]);
+                remaining.remove(i);
+            }
+            // Self intersecting polygons have no ear left
+            None => break,
+        }
+    }
+
+    triangles.extend(fan(&remaining));
+    triangles
+}
+
+/// Lines without their comments along with their 1-based number, a line ending with `\`
+/// continues on the next one
+fn logical_lines(contents: &str) -> Vec<(usize, String)> {
+    let mut lines = Vec::new();
+    let mut continued: Option<(usize, String)> = None;
+
+    for (index, line) in contents.lines().enumerate() {
+        let (number, mut text) = continued.take().unwrap_or((index + 1, String::new()));
+        let line = line.split('#').next().unwrap_or_default();
+
+        match line.trim_end().strip_suffix('\\') {
+            Some(line) => {
+                text.push_str(line);
+                text.push(' ');
+                continued = Some((number, text));
+            }
+            None => {
+                text.push_str(line);
+                lines.push((number, text));
+            }
+        }
+    }
+
+    // A continuation on the last line
+    lines.extend(continued);
+    lines
+}
+
+/// At least `required` numbers, the missing ones are 0 and extra ones are ignored
+fn parse_floats<const N: usize>(arguments: &[&str], required: usize) -> Result<[f64; N], String> {
+    if arguments.len() < required {
+        return Err(format!(
+            "expected {} numbers, found {}",
+            required,
+            arguments.len()
+        ));
+    }
+
+    let mut values = [0.0; N];
+    for (value, argument) in values.iter_mut().zip(arguments) {
+        *value = argument
+            .parse()
+            .map_err(|_| format!("invalid number '{}'", argument))?;
+    }
+    Ok(values)
+}
+
+/// `vertex`, `vertex/uv`, `vertex//normal` or `vertex/uv/normal` for each corner, `counts`
+/// are the numbers of vertices, uvs and normals read so far
+fn parse_face(arguments: &[&str], counts: [usize; 3]) -> Result<Vec<ObjVertex>, String> {
+    if arguments.len() < 3 {
+        return Err(format!(
+            "face has {} vertices, at least 3 are needed",
+            arguments.len()
+        ));
+    }
+
+    arguments
+        .iter()
+        .map(|argument| {
+            let indices: Vec<&str> = argument.split('/').collect();
+            if indices.len() > 3 {
+                return Err(format!("invalid face vertex '{}'", argument));
+            }
+
+            let optional_index = |index: Option<&&str>, count: usize, kind: &str| match index {
+                Some(index) if !index.is_empty() => parse_index(index, count, kind).map(Some),
+                _ => Ok(None),
+            };
+
+            Ok(ObjVertex {
+                vertex: parse_index(indices[0], counts[0], "vertex")?,
+                uv: optional_index(indices.get(1), counts[1], "uv")?,
+                normal: optional_index(indices.get(2), counts[2], "normal")?,
+            })
+        })
+        .collect()
+}
+
+/// 1-based index among the `count` elements read so far, negative ones count back from
+/// the last of them
+fn parse_index(index: &str, count: usize, kind: &str) -> Result<usize, String> {
+    let value: isize = index
+        .parse()
+        .map_err(|_| format!("invalid index '{}'", index))?;
+
+    let resolved = if value < 0 {
+        count as isize + 1 + value
+    } else {
+        value
+    };
+    if resolved < 1 || resolved as usize > count {
+        return Err(format!(
+            "{} index {} is out of range, {} defined so far",
+            kind, value, count
+        ));
+    }
+
+    Ok(resolved as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(contents: &str) -> Result<ObjModel, ObjError> {
+        ObjModel::parse(contents, "test.obj")
+    }
+
+    /// Line and reason of an error on a line
+    fn line_error(error: &ObjError) -> (usize, &str) {
+        match error {
+            ObjError::Line { line, reason, .. } => (*line, reason),
+            ObjError::Read { .. } => panic!("expected a line error, got {}", error),
+        }
+    }
+
+    #[test]
+    fn continuation_lines_are_joined() {
+        let model = parse("v 0 0 0\nv 1 \\\n  2 \\\n  3\nv 0 1 0\nf 1 2 3\n").unwrap();
+        assert_eq!(model.vertices.len(), 3);
+        assert_eq!(model.vertices[1], Vec3(1.0, 2.0, 3.0));
+        assert_eq!(model.faces.len(), 1);
+    }
+
+    #[test]
+    fn errors_report_the_first_line_of_a_continuation() {
+        let error = parse("v 0 0 0\nv 1 \\\n  x 3\n").err().unwrap();
+        assert_eq!(line_error(&error), (2, "invalid number 'x'"));
+        assert_eq!(error.to_string(), "test.obj:2: invalid number 'x'");
+
+        let error = parse("v 0 0 \\\n0\n\nvn 0 1\n").err().unwrap();
+        assert_eq!(line_error(&error).0, 4);
+    }
+
+    #[test]
+    fn trailing_comments_are_ignored() {
+        let model =
+            parse("# header\nv 1 2 3 # first\nv 4 5 6#second\nv 7 8 9\nf 1 2 3 # face\n").unwrap();
+        assert_eq!(model.vertices[0], Vec3(1.0, 2.0, 3.0));
+        assert_eq!(model.vertices[1], Vec3(4.0, 5.0, 6.0));
+        assert_eq!(model.faces[0].vertices.len(), 3);
+    }
+
+    #[test]
+    fn unsupported_keywords_are_warned_about_once() {
+        let model = parse("vp 1\ncstype bezier\nvp 2\nv 0 0 0\n").unwrap();
+        let warnings: Vec<(usize, &str)> = model.warnings.iter().map(line_error).collect();
+        assert_eq!(
+            warnings,
+            [
+                (1, "skipping unsupported vp lines"),
+                (2, "skipping unsupported cstype lines"),
+            ]
+        );
+    }
+
+    #[test]
+    fn missing_material_libraries_are_warnings() {
+        let model = parse("mtllib missing.mtl\nv 0 0 0\n").unwrap();
+        assert_eq!(model.warnings.len(), 1);
+        let (line, reason) = line_error(&model.warnings[0]);
+        assert_eq!(line, 1);
+        assert!(reason.starts_with("failed to load material library"));
+    }
+
+    #[test]
+    fn missing_textures_are_warnings_on_their_mtl_line() {
+        let directory = std::env::temp_dir().join(format!("obj-textures-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let library = directory.join("textured.mtl");
+        fs::write(&library, "newmtl red\nKd 1 0 0\nmap_Kd missing.png\n").unwrap();
+
+        let path = directory.join("textured.obj");
+        let model = ObjModel::parse("mtllib textured.mtl\n", &path.to_string_lossy()).unwrap();
+        fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!(model.materials.len(), 1);
+        assert!(model.materials[0].diffuse_texture.is_none());
+        assert_eq!(model.warnings.len(), 1);
+        assert_eq!(model.warnings[0].path(), library);
+        let (line, reason) = line_error(&model.warnings[0]);
+        assert_eq!(line, 3);
+        assert!(reason.starts_with("failed to load texture"));
+    }
+
+    #[test]
+    fn read_failures_carry_the_path_and_source() {
+        let error = ObjModel::load("missing/model.obj").err().unwrap();
+        match &error {
+            ObjError::Read { path, source } => {
+                assert_eq!(*path, PathBuf::from("missing/model.obj"));
+                assert_eq!(source.kind(), io::ErrorKind::NotFound);
+            }
+            ObjError::Line { .. } => panic!("expected a read error, got {}", error),
+        }
+        assert!(error.source().is_some());
+        assert!(error.to_string().starts_with("missing/model.obj: "));
+    }
+
+    #[test]
+    fn out_of_range_indices_are_errors() {
+        let vertices = "v 0 0 0\nv 1 0 0\nv 0 1 0\n";
+
+        let error = parse(&format!("{}f 0 1 2\n", vertices)).err().unwrap();
+        assert_eq!(
+            line_error(&error),
+            (4, "vertex index 0 is out of range, 3 defined so far")
+        );
+
+        let error = parse(&format!("{}f 1 2 4\n", vertices)).err().unwrap();
+        assert_eq!(
+            line_error(&error).1,
+            "vertex index 4 is out of range, 3 defined so far"
+        );
+
+        let error = parse(&format!("{}vt 0 0\nf 1/2 2/1 3/1\n", vertices))
+            .err()
+            .unwrap();
+        assert_eq!(
+            line_error(&error).1,
+            "uv index 2 is out of range, 1 defined so far"
+        );
+    }
+
+    #[test]
+    fn negative_indices_count_back_from_the_last_element() {
+        let model = parse(
+            "v 0 0 0\nv 1 0 0\nv 0 1 0\nf -1 -2 -3\nvt 0 0\nvn 0 0 1\nv 1 1 0\nf -4/-1/-1 -3/-1/-1 -1/-1/-1\n",
+        )
+        .unwrap();
+        let corners = |face: &ObjFace| -> Vec<(usize, Option<usize>, Option<usize>)> {
+            face.vertices
+                .iter()
+                .map(|corner| (corner.vertex, corner.uv, corner.normal))
+                .collect()
+        };
+        assert_eq!(
+            corners(&model.faces[0]),
+            [(3, None, None), (2, None, None), (1, None, None)]
+        );
+        assert_eq!(
+            corners(&model.faces[1]),
+            [
+                (1, Some(1), Some(1)),
+                (2, Some(1), Some(1)),
+                (4, Some(1), Some(1))
+            ]
+        );
+
+        let error = parse("v 0 0 0\nv 1 0 0\nf -1 -2 -3\n").err().unwrap();
+        assert_eq!(
+            line_error(&error).1,
+            "vertex index -3 is out of range, 2 defined so far"
+        );
+    }
+
+    /// Twice the signed area of the triangles flattened on the z plane, and whether they
+    /// all wind the same way as the polygon
+    fn triangulated_area(points: &[Vec3]) -> (f64, bool) {
+        let area = |[a, b, c]: [Vec3; 3]| {
+            (b.x() - a.x()) * (c.y() - a.y()) - (b.y() - a.y()) * (c.x() - a.x())
+        };
+        let polygon: f64 = (0..points.len())
+            .map(|i| {
+                let (current, next) = (points[i], points[(i + 1) % points.len()]);
+                current.x() * next.y() - next.x() * current.y()
+            })
+            .sum();
+
+        let triangles = triangulate(points);
+        assert_eq!(triangles.len(), points.len() - 2);
+        let areas: Vec<f64> = triangles
+            .iter()
+            .map(|triangle| area(triangle.map(|corner| points[corner])))
+            .collect();
+        let same_winding = areas.iter().all(|a| a.signum() == polygon.signum());
+        assert!((areas.iter().sum::<f64>() - polygon).abs() < 1e-9);
+        (polygon, same_winding)
+    }
+
+    #[test]
+    fn convex_polygons_are_fans() {
+        let square = [
+            Vec3(0.0, 0.0, 0.0),
+            Vec3(1.0, 0.0, 0.0),
+            Vec3(1.0, 1.0, 0.0),
+            Vec3(0.0, 1.0, 0.0),
+        ];
+        assert_eq!(triangulate(&square), [[0, 1, 2], [0, 2, 3]]);
+        assert_eq!(triangulated_area(&square), (2.0, true));
+    }
+
+    #[test]
+    fn concave_quads_are_ear_clipped() {
+        // Arrowhead pointing up with its reflex corner at 1, a fan from 0 would flip its
+        // first triangle
+        let arrowhead = [
+            Vec3(0.0, 0.0, 0.0),
+            Vec3(2.0, 1.0, 0.0),
+            Vec3(4.0, 0.0, 0.0),
+            Vec3(2.0, 3.0, 0.0),
+        ];
+        let (_, same_winding) = triangulated_area(&arrowhead);
+        assert!(same_winding);
+
+        // Clockwise with the reflex corner first
+        let clockwise = [
+            Vec3(2.0, 1.0, 0.0),
+            Vec3(0.0, 0.0, 0.0),
+            Vec3(2.0, 3.0, 0.0),
+            Vec3(4.0, 0.0, 0.0),
+        ];
+        let (area, same_winding) = triangulated_area(&clockwise);
+        assert!(area < 0.0 && same_winding);
+    }
+
+    #[test]
+    fn concave_polygons_off_the_z_plane_are_ear_clipped() {
+        // L shape in the x = 1 plane with its reflex corner second, a fan from the first
+        // corner would flip its first triangle
+        let l_shape = [
+            (2.0, 1.0),
+            (1.0, 1.0),
+            (1.0, 2.0),
+            (0.0, 2.0),
+            (0.0, 0.0),
+            (2.0, 0.0),
+        ]
+        .map(|(y, z)| Vec3(1.0, y, z));
+        let triangles = triangulate(&l_shape);
+        assert_eq!(triangles.len(), 4);
+        // Every triangle winds like the polygon
+        for triangle in triangles {
+            let [a, b, c] = triangle.map(|corner| l_shape[corner]);
+            let normal = (b - a).cross(&(c - a));
+            assert!(normal.x() > 0.0);
+        }
+    }
+
+    #[test]
+    fn polygons_keep_their_outline_in_wireframes() {
+        let model = parse("v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nf 1 2 3 4\n").unwrap();
+        let mesh = model.into_mesh();
+        let edges: Vec<[bool; 3]> = mesh
+            .faces
+            .iter()
+            .map(|face| face.attributes().polygon_edges)
+            .collect();
+        assert_eq!(edges, [[true, true, false], [false, true, true]]);
+    }
+
+    #[test]
+    fn parts_share_their_materials() {
+        let library = std::env::temp_dir().join(format!("parts-{}.mtl", std::process::id()));
+        fs::write(&library, "newmtl red\nKd 1 0 0\n").unwrap();
+        let model = parse(&format!(
+            "mtllib {}\nv 0 0 0\nv 1 0 0\nv 0 1 0\nusemtl red\no a\nf 1 2 3\no b\nf 3 2 1\n",
+            library.display()
+        ));
+        fs::remove_file(&library).unwrap();
+
+        let model = model.unwrap();
+        let parts = model.part_meshes();
+        assert_eq!(parts.len(), 2);
+        for (_, mesh) in parts.iter() {
+            assert_eq!(mesh.materials.len(), 1);
+            assert!(Arc::ptr_eq(&mesh.materials[0], &model.materials[0]));
+            assert_eq!(mesh.faces[0].color(), 0xFFFF0000);
+        }
+    }
+
+    #[test]
+    fn malformed_faces_are_errors() {
+        let vertices = "v 0 0 0\nv 1 0 0\nv 0 1 0\n";
+        assert!(parse(&format!("{}f 1 2\n", vertices)).is_err());
+        assert!(parse(&format!("{}f 1/1/1/1 2 3\n", vertices)).is_err());
+        assert!(parse(&format!("{}f 1 a 3\n", vertices)).is_err());
+    }
+}
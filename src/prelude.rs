@@ -1,4 +1,4 @@
-pub use crate::{Camera, CameraProjection};
-pub use crate::{ClearAuto, ClearColor, Drawable};
-pub use crate::{Engine, EngineConfig, EngineConfigParams, EngineCore, RenderMode};
-pub use crate::{Mesh, Triangle};
+pub use crate::{Camera, CameraProjection, ProjectionType};
+pub use crate::{ClearAuto, ClearColor, ColorBuffer, DepthBuffer, DepthFunc, Drawable};
+pub use crate::{Engine, EngineConfig, EngineConfigParams, EngineCore, Rasterizer, RenderMode};
+pub use crate::{Mesh, Model, Scene, Texture, Triangle};
@@ -1,7 +1,33 @@
-use vecx::{Matrix, Vec3, Vec4};
+use vecx::{Matrix, Vec3, Vec4, VecX};
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ProjectionType {
     Perspective,
+    Orthographic,
+    /// Orthographic seen from a camera looking down at 35.26° and turned by 45°, all three
+    /// axes are foreshortened equally
+    Isometric,
+    /// Orthographic seen from a camera looking down at 30° and turned by 45°, the classic
+    /// 2:1 pixel art view
+    Dimetric,
+}
+
+impl ProjectionType {
+    /// Whether points keep their size regardless of their distance, w is then always 1
+    pub fn is_orthographic(&self) -> bool {
+        *self != ProjectionType::Perspective
+    }
+
+    /// Yaw and pitch to turn the camera to for isometric and dimetric views, see
+    /// `Camera::set_orientation`. None for the other projections
+    pub fn axonometric_angles(&self) -> Option<(f64, f64)> {
+        let yaw = f64::to_radians(45.0);
+        match self {
+            ProjectionType::Isometric => Some((yaw, f64::atan(1.0 / f64::sqrt(2.0)))),
+            ProjectionType::Dimetric => Some((yaw, f64::to_radians(30.0))),
+            _ => None,
+        }
+    }
 }
 
 pub struct CameraProjection {
@@ -37,6 +63,84 @@ impl CameraProjection {
         }
     }
 
+    /// Box shaped view volume, x and y map to the given bounds and z from z_near to z_far
+    pub fn orthographic(
+        left: f64,
+        right: f64,
+        bottom: f64,
+        top: f64,
+        z_near: f64,
+        z_far: f64,
+    ) -> Self {
+        let mut projection_matrix = Matrix::sqr4();
+
+        projection_matrix.set((1, 1), 2.0 / (right - left));
+        projection_matrix.set((1, 4), -(right + left) / (right - left));
+        projection_matrix.set((2, 2), 2.0 / (top - bottom));
+        projection_matrix.set((2, 4), -(top + bottom) / (top - bottom));
+        projection_matrix.set((3, 3), 1.0 / (z_far - z_near));
+        projection_matrix.set((3, 4), -z_near / (z_far - z_near));
+        projection_matrix.set((4, 4), 1.0);
+
+        CameraProjection {
+            aspect_ratio: (top - bottom) / (right - left),
+            fov: 0.0,
+            z_near,
+            z_far,
+            projection_type: ProjectionType::Orthographic,
+            projection_matrix,
+        }
+    }
+
+    /// Orthographic view of a box `half_height` units tall, the camera has to be turned
+    /// to `ProjectionType::axonometric_angles` for the isometric look
+    pub fn isometric(aspect_ratio: f64, half_height: f64, z_near: f64, z_far: f64) -> Self {
+        Self::axonometric(
+            ProjectionType::Isometric,
+            aspect_ratio,
+            half_height,
+            z_near,
+            z_far,
+        )
+    }
+
+    /// Dimetric counterpart of `isometric`
+    pub fn dimetric(aspect_ratio: f64, half_height: f64, z_near: f64, z_far: f64) -> Self {
+        Self::axonometric(
+            ProjectionType::Dimetric,
+            aspect_ratio,
+            half_height,
+            z_near,
+            z_far,
+        )
+    }
+
+    /// Orthographic projection centered on the view direction
+    fn axonometric(
+        projection_type: ProjectionType,
+        aspect_ratio: f64,
+        half_height: f64,
+        z_near: f64,
+        z_far: f64,
+    ) -> Self {
+        let half_width = half_height / aspect_ratio;
+        let mut projection = Self::orthographic(
+            -half_width,
+            half_width,
+            -half_height,
+            half_height,
+            z_near,
+            z_far,
+        );
+        projection.projection_type = projection_type;
+        projection
+    }
+
+    /// Camera space to clip space matrix
+    pub fn matrix(&self) -> &Matrix {
+        &self.projection_matrix
+    }
+
     pub fn projection_type(&self) -> &ProjectionType {
         &self.projection_type
     }
@@ -64,8 +168,14 @@ impl CameraProjection {
     }
 }
 
+/// Viewpoint of the scene. Without any rotation the camera looks down +z with +y up,
+/// yaw turns it around y, then pitch around its x axis (positive looks down) and roll
+/// around its view direction. Angles are in radians
 pub struct Camera {
     position: Vec3,
+    yaw: f64,
+    pitch: f64,
+    roll: f64,
     projection: CameraProjection,
 }
 
@@ -73,21 +183,211 @@ impl Camera {
     pub fn new(position: Vec3, projection: CameraProjection) -> Self {
         Camera {
             position,
+            yaw: 0.0,
+            pitch: 0.0,
+            roll: 0.0,
             projection,
         }
     }
 
+    /// Camera at `position` looking at `target`, rolled so `up` points up on screen
+    pub fn look_at(position: Vec3, target: Vec3, up: Vec3, projection: CameraProjection) -> Self {
+        let mut camera = Camera::new(position, projection);
+        camera.set_target(target, up);
+        camera
+    }
+
     pub fn position(&self) -> &Vec3 {
         &self.position
     }
 
+    pub fn set_position(&mut self, position: Vec3) {
+        self.position = position;
+    }
+
+    pub fn yaw(&self) -> f64 {
+        self.yaw
+    }
+
+    pub fn pitch(&self) -> f64 {
+        self.pitch
+    }
+
+    pub fn roll(&self) -> f64 {
+        self.roll
+    }
+
+    pub fn set_orientation(&mut self, yaw: f64, pitch: f64, roll: f64) {
+        self.yaw = yaw;
+        self.pitch = pitch;
+        self.roll = roll;
+    }
+
+    /// Adds to the current angles
+    pub fn rotate(&mut self, yaw: f64, pitch: f64, roll: f64) {
+        self.set_orientation(self.yaw + yaw, self.pitch + pitch, self.roll + roll);
+    }
+
+    /// Turns the camera towards `target`, rolled so `up` points up on screen. Nothing
+    /// changes when the target is the camera's position
+    pub fn set_target(&mut self, target: Vec3, up: Vec3) {
+        let offset = target - self.position;
+        let length = offset.dot(&offset).sqrt();
+        if length == 0.0 {
+            return;
+        }
+
+        let forward = Vec3(
+            offset.x() / length,
+            offset.y() / length,
+            offset.z() / length,
+        );
+        self.yaw = f64::atan2(forward.x(), forward.z());
+        self.pitch = -f64::asin(forward.y().clamp(-1.0, 1.0));
+        self.roll = 0.0;
+
+        // Roll is measured against the unrolled camera's axes, once `up` is
+        // perpendicular to the view direction
+        let unrolled_up = self.up();
+        let unrolled_right = self.right();
+        let roll = f64::atan2(-up.dot(&unrolled_right), up.dot(&unrolled_up));
+        if roll.is_finite() {
+            self.roll = roll;
+        }
+    }
+
+    /// Unit vector the camera looks along
+    pub fn forward(&self) -> Vec3 {
+        self.axis(Vec3(0.0, 0.0, 1.0))
+    }
+
+    /// Unit vector pointing to the right of the screen
+    pub fn right(&self) -> Vec3 {
+        self.axis(Vec3(1.0, 0.0, 0.0))
+    }
+
+    /// Unit vector pointing to the top of the screen
+    pub fn up(&self) -> Vec3 {
+        self.axis(Vec3(0.0, 1.0, 0.0))
+    }
+
+    /// Camera space axis in world space
+    fn axis(&self, axis: Vec3) -> Vec3 {
+        Vec3::from(&self.orientation_matrix() * &axis.as_mat4(0.0))
+    }
+
+    /// Camera to world rotation, roll then pitch then yaw
+    fn orientation_matrix(&self) -> Matrix {
+        let mut orientation = Matrix::id4();
+        orientation = Matrix::m4_rotate_z(self.roll) * orientation;
+        orientation = Matrix::m4_rotate_x(self.pitch) * orientation;
+        orientation = Matrix::m4_rotate_y(self.yaw) * orientation;
+        orientation
+    }
+
+    /// World to camera space transform, undoes the camera's translation then its rotations
+    /// in reverse order
+    pub fn view_matrix(&self) -> Matrix {
+        let mut view = Matrix::m4_translate(Vec3(
+            -self.position.x(),
+            -self.position.y(),
+            -self.position.z(),
+        ));
+        view = Matrix::m4_rotate_y(-self.yaw) * view;
+        view = Matrix::m4_rotate_x(-self.pitch) * view;
+        view = Matrix::m4_rotate_z(-self.roll) * view;
+        view
+    }
+
+    pub fn projection(&self) -> &CameraProjection {
+        &self.projection
+    }
+
+    pub fn set_projection(&mut self, projection: CameraProjection) {
+        self.projection = projection;
+    }
+
+    /// Projects a camera space point to homogeneous clip space, without the perspective
+    /// divide
+    pub fn project_clip(&self, point: &Vec3) -> Vec4 {
+        self.projection.project(point)
+    }
+
+    /// Projects the point and applies the perspective divide, the returned w is the
+    /// reciprocal of the clip space w so it can be interpolated in screen space.
+    /// Orthographic projections skip the divide, their w is always 1
     pub fn project(&self, point: &Vec3) -> Vec4 {
-        let mut projected = self.projection.project(point);
+        self.normalize(self.projection.project(point))
+    }
+
+    /// Perspective divide of a clip space point, see `project`
+    pub fn normalize(&self, projected: Vec4) -> Vec4 {
+        let w = projected.w();
 
-        if projected.w() != 0.0 {
-            projected /= Vec4::from(projected.w());
+        if self.projection.projection_type.is_orthographic() || w == 0.0 {
+            return projected;
         }
 
-        return projected;
+        Vec4(
+            projected.x() / w,
+            projected.y() / w,
+            projected.z() / w,
+            1.0 / w,
+        )
+    }
+
+    /// Depth of a projected point as stored in the depth buffer, larger is nearer.
+    /// 1/w for perspective projections, orthographic ones have no w to go by so their z
+    /// is reversed instead
+    pub fn depth(&self, projected: &Vec4) -> f64 {
+        if self.projection.projection_type.is_orthographic() {
+            return 1.0 - projected.z();
+        }
+
+        projected.w()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// On screen lengths of the world's unit axes seen by a camera turned for `projection`
+    fn axis_lengths(projection: CameraProjection) -> [f64; 3] {
+        let (yaw, pitch) = projection.projection_type().axonometric_angles().unwrap();
+        let mut camera = Camera::new(Vec3(0.0, 0.0, 0.0), projection);
+        camera.set_orientation(yaw, pitch, 0.0);
+
+        let view = camera.view_matrix();
+        [
+            Vec3(1.0, 0.0, 0.0),
+            Vec3(0.0, 1.0, 0.0),
+            Vec3(0.0, 0.0, 1.0),
+        ]
+        .map(|axis| {
+            let point = Vec3::from(&view * &axis.as_mat4(0.0));
+            (point.x() * point.x() + point.y() * point.y()).sqrt()
+        })
+    }
+
+    #[test]
+    fn axonometric_angles_foreshorten_the_axes() {
+        let [x, y, z] = axis_lengths(CameraProjection::isometric(1.0, 3.0, 0.1, 100.0));
+        assert!((x - y).abs() < 1e-9 && (y - z).abs() < 1e-9);
+
+        let [x, y, z] = axis_lengths(CameraProjection::dimetric(1.0, 3.0, 0.1, 100.0));
+        assert!((x - z).abs() < 1e-9);
+        assert!((x - y).abs() > 0.05);
+    }
+
+    #[test]
+    fn axonometric_projections_are_plain_orthographic_boxes() {
+        let isometric = CameraProjection::isometric(0.5, 3.0, 0.1, 100.0);
+        let orthographic = CameraProjection::orthographic(-6.0, 6.0, -3.0, 3.0, 0.1, 100.0);
+        for point in [Vec3(1.0, 2.0, 3.0), Vec3(-5.0, 0.5, 60.0)] {
+            assert_eq!(isometric.project(&point), orthographic.project(&point));
+        }
+        assert!(isometric.projection_type().is_orthographic());
+        assert_eq!(ProjectionType::Orthographic.axonometric_angles(), None);
     }
 }
@@ -0,0 +1,399 @@
+use std::ops::{Mul, MulAssign};
+
+use vecx::{Matrix, Vec3, VecX};
+
+/// Sequence Euler angles are applied in, `XYZ` rotates around x first and z last
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EulerOrder {
+    XYZ,
+    XZY,
+    YXZ,
+    YZX,
+    ZXY,
+    ZYX,
+}
+
+impl EulerOrder {
+    /// Axis indices in the order they are applied
+    fn axes(&self) -> [usize; 3] {
+        match self {
+            EulerOrder::XYZ => [0, 1, 2],
+            EulerOrder::XZY => [0, 2, 1],
+            EulerOrder::YXZ => [1, 0, 2],
+            EulerOrder::YZX => [1, 2, 0],
+            EulerOrder::ZXY => [2, 0, 1],
+            EulerOrder::ZYX => [2, 1, 0],
+        }
+    }
+
+    /// 1 for the cyclic orders, -1 for the others
+    fn parity(&self) -> f64 {
+        match self {
+            EulerOrder::XYZ | EulerOrder::YZX | EulerOrder::ZXY => 1.0,
+            _ => -1.0,
+        }
+    }
+}
+
+/// Rotation stored as a unit quaternion `w + xi + yj + zk`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Quaternion {
+    pub w: f64,
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+}
+
+impl Quaternion {
+    pub const IDENTITY: Quaternion = Quaternion {
+        w: 1.0,
+        x: 0.0,
+        y: 0.0,
+        z: 0.0,
+    };
+
+    pub fn new(w: f64, x: f64, y: f64, z: f64) -> Self {
+        Quaternion { w, x, y, z }
+    }
+
+    /// Rotation of `angle` radians around `axis`, a zero axis gives the identity
+    pub fn from_axis_angle(axis: Vec3, angle: f64) -> Self {
+        let length = axis.dot(&axis).sqrt();
+        if length == 0.0 {
+            return Self::IDENTITY;
+        }
+
+        let s = (angle / 2.0).sin() / length;
+        Quaternion::new(
+            (angle / 2.0).cos(),
+            axis.x() * s,
+            axis.y() * s,
+            axis.z() * s,
+        )
+    }
+
+    /// Axis and angle in radians of the rotation, the axis is x for the identity
+    pub fn to_axis_angle(&self) -> (Vec3, f64) {
+        let q = self.normalized();
+        let angle = 2.0 * q.w.clamp(-1.0, 1.0).acos();
+        let s = (1.0 - q.w * q.w).max(0.0).sqrt();
+        if s < 1e-9 {
+            return (Vec3(1.0, 0.0, 0.0), angle);
+        }
+
+        (Vec3(q.x / s, q.y / s, q.z / s), angle)
+    }
+
+    /// Rotations around x, y and z by the angles' components, applied in `order`
+    pub fn from_euler(angles: Vec3, order: EulerOrder) -> Self {
+        let angles = [angles.x(), angles.y(), angles.z()];
+        let axes = [
+            Vec3(1.0, 0.0, 0.0),
+            Vec3(0.0, 1.0, 0.0),
+            Vec3(0.0, 0.0, 1.0),
+        ];
+
+        order.axes().iter().fold(Self::IDENTITY, |rotation, axis| {
+            Self::from_axis_angle(axes[*axis], angles[*axis]) * rotation
+        })
+    }
+
+    /// Euler angles that `from_euler` turns back into this rotation. Past gimbal lock the
+    /// last rotation is folded into the first
+    pub fn to_euler(&self, order: EulerOrder) -> Vec3 {
+        let m = self.rotation_rows();
+        let [i, j, k] = order.axes();
+        let s = order.parity();
+
+        let mut angles = [0.0; 3];
+        let sin_j = (-s * m[k][i]).clamp(-1.0, 1.0);
+        angles[j] = sin_j.asin();
+
+        if sin_j.abs() < 1.0 - 1e-9 {
+            angles[i] = f64::atan2(s * m[k][j], m[k][k]);
+            angles[k] = f64::atan2(s * m[j][i], m[i][i]);
+        } else {
+            angles[i] = f64::atan2(-s * m[j][k], m[j][j]);
+        }
+
+        Vec3(angles[0], angles[1], angles[2])
+    }
+
+    pub fn length(&self) -> f64 {
+        (self.w * self.w + self.x * self.x + self.y * self.y + self.z * self.z).sqrt()
+    }
+
+    /// Same rotation scaled back to unit length, rounding errors pile up when rotations
+    /// are composed every frame
+    pub fn normalized(&self) -> Self {
+        let length = self.length();
+        if length == 0.0 {
+            return Self::IDENTITY;
+        }
+
+        Quaternion::new(
+            self.w / length,
+            self.x / length,
+            self.y / length,
+            self.z / length,
+        )
+    }
+
+    /// Inverse rotation of a unit quaternion
+    pub fn conjugate(&self) -> Self {
+        Quaternion::new(self.w, -self.x, -self.y, -self.z)
+    }
+
+    pub fn dot(&self, other: &Quaternion) -> f64 {
+        self.w * other.w + self.x * other.x + self.y * other.y + self.z * other.z
+    }
+
+    /// Spherical interpolation along the shortest arc, `t` = 0 gives `self` and 1 `other`
+    pub fn slerp(&self, other: &Quaternion, t: f64) -> Self {
+        let mut other = *other;
+        let mut cos_angle = self.dot(&other);
+        if cos_angle < 0.0 {
+            other = Quaternion::new(-other.w, -other.x, -other.y, -other.z);
+            cos_angle = -cos_angle;
+        }
+
+        // Nearly identical rotations, sin(angle) would be too small to divide by
+        let (a, b) = if cos_angle > 1.0 - 1e-6 {
+            (1.0 - t, t)
+        } else {
+            let angle = cos_angle.acos();
+            let sin_angle = angle.sin();
+            (
+                ((1.0 - t) * angle).sin() / sin_angle,
+                (t * angle).sin() / sin_angle,
+            )
+        };
+
+        Quaternion::new(
+            self.w * a + other.w * b,
+            self.x * a + other.x * b,
+            self.y * a + other.y * b,
+            self.z * a + other.z * b,
+        )
+        .normalized()
+    }
+
+    pub fn rotate_vector(&self, vector: Vec3) -> Vec3 {
+        let m = self.rotation_rows();
+        let v = [vector.x(), vector.y(), vector.z()];
+        let row = |r: [f64; 3]| r[0] * v[0] + r[1] * v[1] + r[2] * v[2];
+        Vec3(row(m[0]), row(m[1]), row(m[2]))
+    }
+
+    /// 4x4 rotation matrix, like the ones built by `Matrix::m4_rotate_x`
+    pub fn to_matrix(&self) -> Matrix {
+        let m = self.rotation_rows();
+        let mut matrix = Matrix::sqr4();
+        for (row, values) in m.iter().enumerate() {
+            for (col, value) in values.iter().enumerate() {
+                matrix.set((row + 1, col + 1), *value);
+            }
+        }
+        matrix.set((4, 4), 1.0);
+        matrix
+    }
+
+    /// 3x3 rotation matrix, row by row
+    fn rotation_rows(&self) -> [[f64; 3]; 3] {
+        let Quaternion { w, x, y, z } = self.normalized();
+        [
+            [
+                1.0 - 2.0 * (y * y + z * z),
+                2.0 * (x * y - w * z),
+                2.0 * (x * z + w * y),
+            ],
+            [
+                2.0 * (x * y + w * z),
+                1.0 - 2.0 * (x * x + z * z),
+                2.0 * (y * z - w * x),
+            ],
+            [
+                2.0 * (x * z - w * y),
+                2.0 * (y * z + w * x),
+                1.0 - 2.0 * (x * x + y * y),
+            ],
+        ]
+    }
+}
+
+impl Default for Quaternion {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+/// `a * b` rotates by `b` first, then by `a`
+impl Mul for Quaternion {
+    type Output = Quaternion;
+
+    fn mul(self, b: Quaternion) -> Quaternion {
+        let a = self;
+        Quaternion::new(
+            a.w * b.w - a.x * b.x - a.y * b.y - a.z * b.z,
+            a.w * b.x + a.x * b.w + a.y * b.z - a.z * b.y,
+            a.w * b.y - a.x * b.z + a.y * b.w + a.z * b.x,
+            a.w * b.z + a.x * b.y - a.y * b.x + a.z * b.w,
+        )
+    }
+}
+
+impl MulAssign for Quaternion {
+    fn mul_assign(&mut self, other: Quaternion) {
+        *self = *self * other;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::{FRAC_PI_2, PI};
+
+    use super::*;
+
+    const ORDERS: [EulerOrder; 6] = [
+        EulerOrder::XYZ,
+        EulerOrder::XZY,
+        EulerOrder::YXZ,
+        EulerOrder::YZX,
+        EulerOrder::ZXY,
+        EulerOrder::ZYX,
+    ];
+
+    fn assert_close(a: f64, b: f64) {
+        assert!((a - b).abs() < 1e-9, "{} != {}", a, b);
+    }
+
+    fn assert_vec_close(a: Vec3, b: Vec3) {
+        for (a, b) in [(a.x(), b.x()), (a.y(), b.y()), (a.z(), b.z())] {
+            assert_close(a, b);
+        }
+    }
+
+    /// `q` and `-q` are the same rotation
+    fn assert_same_rotation(a: &Quaternion, b: &Quaternion) {
+        assert_close(a.dot(b).abs(), 1.0);
+    }
+
+    /// Angle of each axis, the middle one of each order kept off gimbal lock
+    fn angle_samples() -> Vec<Vec3> {
+        let values = [-2.5, -1.2, -0.3, 0.0, 0.7, 1.4, 3.0];
+        let mut samples = Vec::new();
+        for x in values {
+            for y in values {
+                for z in values {
+                    samples.push(Vec3(x, y, z));
+                }
+            }
+        }
+        samples
+    }
+
+    #[test]
+    fn euler_angles_round_trip() {
+        for order in ORDERS {
+            let middle = order.axes()[1];
+            for angles in angle_samples() {
+                let components = [angles.x(), angles.y(), angles.z()];
+                if components[middle].abs() >= FRAC_PI_2 {
+                    continue;
+                }
+
+                let rotation = Quaternion::from_euler(angles, order);
+                assert_vec_close(rotation.to_euler(order), angles);
+            }
+        }
+    }
+
+    #[test]
+    fn euler_angles_keep_the_rotation_past_gimbal_lock() {
+        for order in ORDERS {
+            for angles in angle_samples() {
+                let rotation = Quaternion::from_euler(angles, order);
+                let back = Quaternion::from_euler(rotation.to_euler(order), order);
+                assert_same_rotation(&rotation, &back);
+            }
+
+            let mut locked = [0.4, 0.4, 0.4];
+            locked[order.axes()[1]] = FRAC_PI_2;
+            let rotation = Quaternion::from_euler(Vec3(locked[0], locked[1], locked[2]), order);
+            let back = Quaternion::from_euler(rotation.to_euler(order), order);
+            assert_same_rotation(&rotation, &back);
+        }
+    }
+
+    #[test]
+    fn euler_orders_apply_the_first_axis_first() {
+        // A quarter turn around x takes y to z, which the turn around z then keeps
+        let rotation = Quaternion::from_euler(Vec3(FRAC_PI_2, 0.0, FRAC_PI_2), EulerOrder::XYZ);
+        assert_vec_close(
+            rotation.rotate_vector(Vec3(0.0, 1.0, 0.0)),
+            Vec3(0.0, 0.0, 1.0),
+        );
+        // Around z first, y goes to -x which the turn around x keeps
+        let rotation = Quaternion::from_euler(Vec3(FRAC_PI_2, 0.0, FRAC_PI_2), EulerOrder::ZYX);
+        assert_vec_close(
+            rotation.rotate_vector(Vec3(0.0, 1.0, 0.0)),
+            Vec3(-1.0, 0.0, 0.0),
+        );
+    }
+
+    #[test]
+    fn axis_angle_round_trips() {
+        let axis = Vec3(1.0, 2.0, -2.0);
+        let rotation = Quaternion::from_axis_angle(axis, 1.3);
+        let (back_axis, angle) = rotation.to_axis_angle();
+        assert_vec_close(back_axis, Vec3(1.0 / 3.0, 2.0 / 3.0, -2.0 / 3.0));
+        assert_close(angle, 1.3);
+
+        assert_eq!(
+            Quaternion::from_axis_angle(Vec3::ZERO, 1.0),
+            Quaternion::IDENTITY
+        );
+        assert_eq!(
+            Quaternion::IDENTITY.to_axis_angle(),
+            (Vec3(1.0, 0.0, 0.0), 0.0)
+        );
+    }
+
+    #[test]
+    fn products_rotate_by_the_right_operand_first() {
+        let x = Quaternion::from_axis_angle(Vec3(1.0, 0.0, 0.0), FRAC_PI_2);
+        let z = Quaternion::from_axis_angle(Vec3(0.0, 0.0, 1.0), FRAC_PI_2);
+        let point = Vec3(0.0, 1.0, 0.0);
+        assert_vec_close(
+            (z * x).rotate_vector(point),
+            z.rotate_vector(x.rotate_vector(point)),
+        );
+        assert_same_rotation(&(x * x.conjugate()), &Quaternion::IDENTITY);
+    }
+
+    #[test]
+    fn slerp_follows_the_shortest_arc() {
+        let axis = Vec3(0.0, 1.0, 0.0);
+        let from = Quaternion::from_axis_angle(axis, 0.2);
+        let to = Quaternion::from_axis_angle(axis, 1.4);
+
+        assert_same_rotation(&from.slerp(&to, 0.0), &from);
+        assert_same_rotation(&from.slerp(&to, 1.0), &to);
+        assert_same_rotation(
+            &from.slerp(&to, 0.25),
+            &Quaternion::from_axis_angle(axis, 0.5),
+        );
+
+        // -to is the same rotation the long way around
+        let negated = Quaternion::new(-to.w, -to.x, -to.y, -to.z);
+        assert_same_rotation(
+            &from.slerp(&negated, 0.5),
+            &Quaternion::from_axis_angle(axis, 0.8),
+        );
+
+        // Opposite half turns are nearly identical rotations
+        let half_turn = Quaternion::from_axis_angle(axis, PI);
+        let almost = Quaternion::from_axis_angle(axis, PI + 1e-8);
+        assert_same_rotation(&half_turn.slerp(&almost, 0.5), &half_turn);
+    }
+}
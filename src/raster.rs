@@ -0,0 +1,547 @@
+use std::ops::Range;
+
+use vecx::{Vec2, VecX};
+
+/// Bits of sub-pixel precision used to snap vertices to a fixed point grid
+const SUBPIXEL_BITS: u32 = 8;
+const SUBPIXEL_STEP: i64 = 1 << SUBPIXEL_BITS;
+/// Vertices further than this from the origin would overflow the edge functions
+const MAX_COORDINATE: f64 = (1 << 22) as f64;
+
+/// Weights of a pixel relative to the three points of the triangle being rasterized,
+/// they sum up to 1.0
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Barycentric(pub f64, pub f64, pub f64);
+
+impl Barycentric {
+    /// Interpolates a per-vertex attribute at this pixel
+    pub fn interpolate(&self, a: f64, b: f64, c: f64) -> f64 {
+        self.0 * a + self.1 * b + self.2 * c
+    }
+
+    pub fn weights(&self) -> [f64; 3] {
+        [self.0, self.1, self.2]
+    }
+
+    /// Screen space weights turned into weights for attributes of points at the given
+    /// 1/w, which makes the interpolation perspective correct
+    pub fn perspective_corrected(&self, inverse_w: [f64; 3]) -> Barycentric {
+        let [a, b, c] = inverse_w;
+        let inverse_w = self.interpolate(a, b, c);
+        if inverse_w == 0.0 {
+            return *self;
+        }
+
+        Barycentric(
+            self.0 * a / inverse_w,
+            self.1 * b / inverse_w,
+            self.2 * c / inverse_w,
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct FixedPoint {
+    x: i64,
+    y: i64,
+}
+
+impl FixedPoint {
+    fn new(point: Vec2) -> Self {
+        FixedPoint {
+            x: (point.x() * SUBPIXEL_STEP as f64).round() as i64,
+            y: (point.y() * SUBPIXEL_STEP as f64).round() as i64,
+        }
+    }
+}
+
+/// Which side of the edge `a`-`b` the point `p` lies on, scaled by twice the area of `abp`
+fn edge_function(a: FixedPoint, b: FixedPoint, p: FixedPoint) -> i64 {
+    (b.x - a.x) * (p.y - a.y) - (b.y - a.y) * (p.x - a.x)
+}
+
+/// With y pointing down and a positive area, top edges are horizontal going right and
+/// left edges are going up
+fn is_top_left(a: FixedPoint, b: FixedPoint) -> bool {
+    let dx = b.x - a.x;
+    let dy = b.y - a.y;
+    (dy == 0 && dx > 0) || dy < 0
+}
+
+/// Edge function rasterizer, walks the triangle's bounding box and calls `plot` for every
+/// pixel whose center is covered. Pixels lying exactly on an edge are only drawn for top
+/// and left edges so triangles sharing an edge never draw the same pixel twice
+pub fn rasterize_triangle<F: FnMut(usize, usize, Barycentric)>(
+    a: Vec2,
+    b: Vec2,
+    c: Vec2,
+    width: usize,
+    height: usize,
+    plot: F,
+) {
+    rasterize_triangle_rows(a, b, c, width, 0..height, plot);
+}
+
+/// `rasterize_triangle` limited to a range of rows, pixels get the same weights whatever
+/// the range
+pub(crate) fn rasterize_triangle_rows<F: FnMut(usize, usize, Barycentric)>(
+    a: Vec2,
+    b: Vec2,
+    c: Vec2,
+    width: usize,
+    rows: Range<usize>,
+    mut plot: F,
+) {
+    if width == 0 || rows.is_empty() {
+        return;
+    }
+
+    let out_of_range = |v: f64| v.is_nan() || v.abs() >= MAX_COORDINATE;
+    if [a, b, c]
+        .iter()
+        .any(|p| out_of_range(p.x()) || out_of_range(p.y()))
+    {
+        return;
+    }
+
+    let mut v0 = FixedPoint::new(a);
+    let mut v1 = FixedPoint::new(b);
+    let v2 = FixedPoint::new(c);
+
+    let mut area = edge_function(v0, v1, v2);
+    if area == 0 {
+        return;
+    }
+
+    // Walk counter-clockwise triangles with their first two points swapped
+    let flipped = area < 0;
+    if flipped {
+        std::mem::swap(&mut v0, &mut v1);
+        area = -area;
+    }
+
+    let bias0 = if is_top_left(v1, v2) { 0 } else { -1 };
+    let bias1 = if is_top_left(v2, v0) { 0 } else { -1 };
+    let bias2 = if is_top_left(v0, v1) { 0 } else { -1 };
+
+    let min_x = v0.x.min(v1.x).min(v2.x);
+    let max_x = v0.x.max(v1.x).max(v2.x);
+    let min_y = v0.y.min(v1.y).min(v2.y);
+    let max_y = v0.y.max(v1.y).max(v2.y);
+
+    let start_x = (min_x >> SUBPIXEL_BITS).max(0);
+    let end_x = (max_x >> SUBPIXEL_BITS).min(width as i64 - 1);
+    let start_y = (min_y >> SUBPIXEL_BITS).max(rows.start as i64);
+    let end_y = (max_y >> SUBPIXEL_BITS).min(rows.end as i64 - 1);
+
+    if start_x > end_x || start_y > end_y {
+        return;
+    }
+
+    // Edge function increments when moving one pixel right or down
+    let step_x0 = -(v2.y - v1.y) * SUBPIXEL_STEP;
+    let step_x1 = -(v0.y - v2.y) * SUBPIXEL_STEP;
+    let step_x2 = -(v1.y - v0.y) * SUBPIXEL_STEP;
+    let step_y0 = (v2.x - v1.x) * SUBPIXEL_STEP;
+    let step_y1 = (v0.x - v2.x) * SUBPIXEL_STEP;
+    let step_y2 = (v1.x - v0.x) * SUBPIXEL_STEP;
+
+    // Sample at pixel centers
+    let origin = FixedPoint {
+        x: (start_x << SUBPIXEL_BITS) + SUBPIXEL_STEP / 2,
+        y: (start_y << SUBPIXEL_BITS) + SUBPIXEL_STEP / 2,
+    };
+    let mut row0 = edge_function(v1, v2, origin);
+    let mut row1 = edge_function(v2, v0, origin);
+    let mut row2 = edge_function(v0, v1, origin);
+
+    let area = area as f64;
+
+    for y in start_y..=end_y {
+        let mut w0 = row0;
+        let mut w1 = row1;
+        let mut w2 = row2;
+
+        for x in start_x..=end_x {
+            if w0 + bias0 >= 0 && w1 + bias1 >= 0 && w2 + bias2 >= 0 {
+                let l0 = w0 as f64 / area;
+                let l1 = w1 as f64 / area;
+                let l2 = w2 as f64 / area;
+
+                let weights = if flipped {
+                    Barycentric(l1, l0, l2)
+                } else {
+                    Barycentric(l0, l1, l2)
+                };
+
+                plot(x as usize, y as usize, weights);
+            }
+
+            w0 += step_x0;
+            w1 += step_x1;
+            w2 += step_x2;
+        }
+
+        row0 += step_y0;
+        row1 += step_y1;
+        row2 += step_y2;
+    }
+}
+
+/// Walks every pixel whose center is covered by the triangle row by row, weights are
+/// sampled at pixel centers like `rasterize_triangle`. Only rows within `rows` are walked,
+/// each row is computed on its own so pixels are the same whatever the range
+pub(crate) fn scan_triangle<F: FnMut(usize, usize, Barycentric)>(
+    a: Vec2,
+    b: Vec2,
+    c: Vec2,
+    width: usize,
+    rows: Range<usize>,
+    mut plot: F,
+) {
+    let mut points = [a, b, c];
+    if points
+        .iter()
+        .any(|p| !p.x().is_finite() || !p.y().is_finite())
+    {
+        return;
+    }
+
+    // Twice the signed area, zero for degenerate triangles
+    let area = (b.y() - c.y()) * (a.x() - c.x()) + (c.x() - b.x()) * (a.y() - c.y());
+    if area == 0.0 {
+        return;
+    }
+
+    let barycentric = |x: f64, y: f64| {
+        let l0 = ((b.y() - c.y()) * (x - c.x()) + (c.x() - b.x()) * (y - c.y())) / area;
+        let l1 = ((c.y() - a.y()) * (x - c.x()) + (a.x() - c.x()) * (y - c.y())) / area;
+        Barycentric(l0, l1, 1.0 - l0 - l1)
+    };
+
+    points.sort_by(|p, q| p.y().total_cmp(&q.y()));
+    let [top, middle, bottom] = points;
+
+    // First pixel whose center lies at or after `v`
+    let first_pixel = |v: f64| (v - 0.5).ceil();
+
+    let y_start = first_pixel(top.y()).max(rows.start as f64);
+    let y_end = first_pixel(bottom.y()).min(rows.end as f64);
+
+    let mut y = y_start;
+    while y < y_end {
+        let center_y = y + 0.5;
+        let long_x = edge_x(top, bottom, center_y);
+        let short_x = if center_y < middle.y() {
+            edge_x(top, middle, center_y)
+        } else {
+            edge_x(middle, bottom, center_y)
+        };
+
+        let (left, right) = if long_x <= short_x {
+            (long_x, short_x)
+        } else {
+            (short_x, long_x)
+        };
+
+        let start = first_pixel(left).max(0.0) as usize;
+        let end = first_pixel(right).min(width as f64) as usize;
+
+        for x in start..end {
+            plot(x, y as usize, barycentric(x as f64 + 0.5, center_y));
+        }
+
+        y += 1.0;
+    }
+}
+
+/// x coordinate of the edge `p`-`q` at height `y`
+fn edge_x(p: Vec2, q: Vec2, y: f64) -> f64 {
+    let height = q.y() - p.y();
+    if height == 0.0 {
+        return p.x();
+    }
+
+    let t = ((y - p.y()) / height).clamp(0.0, 1.0);
+    p.x() + (q.x() - p.x()) * t
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    const WIDTH: usize = 24;
+    const HEIGHT: usize = 20;
+
+    /// Ways of walking a triangle, the row limited ones a few rows at a time
+    #[derive(Debug, Clone, Copy)]
+    enum Walk {
+        EdgeFunction,
+        EdgeFunctionRows,
+        Scanline,
+    }
+
+    const WALKS: [Walk; 3] = [Walk::EdgeFunction, Walk::EdgeFunctionRows, Walk::Scanline];
+
+    /// Pixels plotted for the triangle in plotting order, checking they are on screen
+    fn plotted(walk: Walk, [a, b, c]: [Vec2; 3]) -> Vec<(usize, usize, Barycentric)> {
+        let mut pixels = Vec::new();
+        let mut walk_rows = |rows: Range<usize>| {
+            let mut plot = |x: usize, y: usize, weights: Barycentric| {
+                assert!(
+                    x < WIDTH && rows.contains(&y),
+                    "{:?} plotted ({}, {})",
+                    walk,
+                    x,
+                    y
+                );
+                pixels.push((x, y, weights));
+            };
+            match walk {
+                Walk::EdgeFunction => rasterize_triangle(a, b, c, WIDTH, HEIGHT, plot),
+                Walk::EdgeFunctionRows => {
+                    rasterize_triangle_rows(a, b, c, WIDTH, rows.clone(), &mut plot)
+                }
+                Walk::Scanline => scan_triangle(a, b, c, WIDTH, rows.clone(), &mut plot),
+            }
+        };
+        match walk {
+            Walk::EdgeFunction => walk_rows(0..HEIGHT),
+            _ => (0..HEIGHT)
+                .step_by(3)
+                .for_each(|start| walk_rows(start..(start + 3).min(HEIGHT))),
+        }
+        pixels
+    }
+
+    fn covered(walk: Walk, triangle: [Vec2; 3]) -> Vec<(usize, usize)> {
+        let mut pixels: Vec<(usize, usize)> = plotted(walk, triangle)
+            .into_iter()
+            .map(|(x, y, _)| (x, y))
+            .collect();
+        pixels.sort_unstable();
+        pixels
+    }
+
+    #[test]
+    fn known_triangle_covers_the_pixel_centers_inside_it() {
+        let triangle = [Vec2(0.0, 0.0), Vec2(4.0, 0.0), Vec2(0.0, 4.0)];
+        // Centers on the hypotenuse belong to a bottom right edge and are left out
+        let expected = vec![(0, 0), (0, 1), (0, 2), (1, 0), (1, 1), (2, 0)];
+
+        for walk in WALKS {
+            assert_eq!(covered(walk, triangle), expected, "{:?}", walk);
+
+            let (_, _, weights) = plotted(walk, triangle)
+                .into_iter()
+                .find(|&(x, y, _)| (x, y) == (0, 0))
+                .unwrap();
+            let [a, b, c] = weights.weights();
+            assert!(
+                (a - 0.75).abs() < 1e-9 && (b - 0.125).abs() < 1e-9 && (c - 0.125).abs() < 1e-9
+            );
+
+            // Winding doesn't matter, weights follow the points
+            let [a, b, c] = triangle;
+            assert_eq!(covered(walk, [b, a, c]), expected, "{:?}", walk);
+        }
+    }
+
+    #[test]
+    fn degenerate_triangles_draw_nothing() {
+        let triangles = [
+            // Collinear
+            [Vec2(1.0, 1.0), Vec2(5.0, 5.0), Vec2(9.0, 9.0)],
+            [Vec2(2.0, 3.0), Vec2(12.0, 3.0), Vec2(7.0, 3.0)],
+            // Two or three points in the same place
+            [Vec2(4.0, 4.0), Vec2(4.0, 4.0), Vec2(10.0, 8.0)],
+            [Vec2(6.5, 6.5), Vec2(6.5, 6.5), Vec2(6.5, 6.5)],
+            // Too small to cover a pixel center
+            [Vec2(3.1, 3.1), Vec2(3.4, 3.1), Vec2(3.1, 3.4)],
+            // Not finite
+            [Vec2(f64::NAN, 1.0), Vec2(5.0, 5.0), Vec2(1.0, 9.0)],
+            [Vec2(1.0, 1.0), Vec2(f64::INFINITY, 5.0), Vec2(1.0, 9.0)],
+        ];
+
+        for walk in WALKS {
+            for triangle in triangles {
+                assert!(
+                    covered(walk, triangle).is_empty(),
+                    "{:?} {:?}",
+                    walk,
+                    triangle
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn off_screen_triangles_draw_nothing() {
+        let triangles = [
+            [Vec2(-10.0, 2.0), Vec2(-1.0, 2.0), Vec2(-5.0, 10.0)],
+            [Vec2(2.0, -10.0), Vec2(10.0, -1.0), Vec2(5.0, -6.0)],
+            [Vec2(30.0, 2.0), Vec2(40.0, 2.0), Vec2(35.0, 10.0)],
+            [Vec2(2.0, 25.0), Vec2(10.0, 30.0), Vec2(5.0, 40.0)],
+            // Far enough to overflow the fixed point edge functions
+            [Vec2(-1e30, 0.0), Vec2(-1e30, 10.0), Vec2(-2e30, 5.0)],
+        ];
+
+        for walk in WALKS {
+            for triangle in triangles {
+                assert!(
+                    covered(walk, triangle).is_empty(),
+                    "{:?} {:?}",
+                    walk,
+                    triangle
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn triangles_are_clipped_to_the_screen() {
+        // Reaches past every side of the screen
+        let triangle = [Vec2(-6.0, -5.0), Vec2(40.0, 4.0), Vec2(3.0, 31.0)];
+        let [a, b, c] = triangle;
+        let inside = |x: f64, y: f64| {
+            let edge =
+                |p: Vec2, q: Vec2| (q.x() - p.x()) * (y - p.y()) - (q.y() - p.y()) * (x - p.x());
+            edge(a, b) > 0.0 && edge(b, c) > 0.0 && edge(c, a) > 0.0
+        };
+        let mut expected = Vec::new();
+        for x in 0..WIDTH {
+            for y in 0..HEIGHT {
+                if inside(x as f64 + 0.5, y as f64 + 0.5) {
+                    expected.push((x, y));
+                }
+            }
+        }
+        assert!(expected.len() > 100);
+
+        for walk in WALKS {
+            assert_eq!(covered(walk, triangle), expected, "{:?}", walk);
+        }
+    }
+
+    /// How many times each pixel was plotted for all the triangles
+    fn coverage(walk: Walk, triangles: &[[Vec2; 3]]) -> HashMap<(usize, usize), usize> {
+        let mut counts = HashMap::new();
+        for &triangle in triangles {
+            for pixel in covered(walk, triangle) {
+                *counts.entry(pixel).or_insert(0) += 1;
+            }
+        }
+        counts
+    }
+
+    /// Checks the pixels whose centers lie inside `min`-`max` are covered exactly once and
+    /// no pixel twice
+    fn assert_covered_once(walk: Walk, triangles: &[[Vec2; 3]], min: Vec2, max: Vec2) {
+        let counts = coverage(walk, triangles);
+        assert!(
+            counts.values().all(|&count| count == 1),
+            "{:?} {:?}",
+            walk,
+            counts
+        );
+
+        let rows = min.y().ceil() as usize..max.y().floor() as usize;
+        let columns = min.x().ceil() as usize..max.x().floor() as usize;
+        for y in rows {
+            for x in columns.clone() {
+                assert!(
+                    counts.contains_key(&(x, y)),
+                    "{:?} missed ({}, {})",
+                    walk,
+                    x,
+                    y
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn shared_edges_through_pixel_centers_are_covered_once() {
+        // Rhombi split along an edge running through a row or column of pixel centers
+        let horizontal = [
+            [Vec2(0.5, 4.5), Vec2(10.5, 4.5), Vec2(5.5, 0.5)],
+            [Vec2(0.5, 4.5), Vec2(10.5, 4.5), Vec2(5.5, 8.5)],
+        ];
+        let vertical = [
+            [Vec2(4.5, 0.5), Vec2(4.5, 10.5), Vec2(0.5, 5.5)],
+            [Vec2(4.5, 0.5), Vec2(4.5, 10.5), Vec2(8.5, 5.5)],
+        ];
+
+        for walk in WALKS {
+            for triangles in [horizontal, vertical] {
+                let counts = coverage(walk, &triangles);
+                assert!(counts.values().all(|&count| count == 1), "{:?}", walk);
+            }
+
+            // Centers on the shared edge, except its ends
+            let counts = coverage(walk, &horizontal);
+            assert!((1..10).all(|x| counts.contains_key(&(x, 4))), "{:?}", walk);
+            let counts = coverage(walk, &vertical);
+            assert!((1..10).all(|y| counts.contains_key(&(4, y))), "{:?}", walk);
+        }
+    }
+
+    #[test]
+    fn meshes_cover_every_pixel_once() {
+        // Grid of quads with jittered inner corners, split along alternating diagonals
+        let (columns, rows) = (5, 4);
+        let corner = |i: usize, j: usize| {
+            let jitter = |k: usize| match k % 4 {
+                0 => 0.0,
+                1 => 0.5,
+                2 => -0.37,
+                _ => 1.21,
+            };
+            let inner_x = i > 0 && i < columns;
+            let inner_y = j > 0 && j < rows;
+            Vec2(
+                1.0 + i as f64 * 4.0 + if inner_x { jitter(i * 3 + j) } else { 0.0 },
+                1.0 + j as f64 * 4.0 + if inner_y { jitter(i + j * 5) } else { 0.0 },
+            )
+        };
+
+        let mut triangles = Vec::new();
+        for j in 0..rows {
+            for i in 0..columns {
+                let (a, b) = (corner(i, j), corner(i + 1, j));
+                let (c, d) = (corner(i + 1, j + 1), corner(i, j + 1));
+                if (i + j) % 2 == 0 {
+                    triangles.extend([[a, b, c], [a, c, d]]);
+                } else {
+                    triangles.extend([[a, b, d], [b, d, c]]);
+                }
+            }
+        }
+
+        let max = corner(columns, rows);
+        for walk in WALKS {
+            assert_covered_once(walk, &triangles, Vec2(1.0, 1.0), max);
+        }
+    }
+
+    #[test]
+    fn vertices_snap_to_the_subpixel_grid() {
+        // Edges running through pixel centers
+        let triangle = [Vec2(0.5, 0.5), Vec2(8.5, 0.5), Vec2(0.5, 8.5)];
+        let expected = covered(Walk::EdgeFunction, triangle);
+
+        // Less than half a step away snaps to the same points
+        let nudge = 0.4 / SUBPIXEL_STEP as f64;
+        let nudged = triangle.map(|p| Vec2(p.x() + nudge, p.y() - nudge));
+        assert_eq!(covered(Walk::EdgeFunction, nudged), expected);
+
+        // A whole step moves the top and left edges past the first row and column
+        let step = 1.0 / SUBPIXEL_STEP as f64;
+        let moved = triangle.map(|p| Vec2(p.x() + step, p.y() + step));
+        let moved = covered(Walk::EdgeFunction, moved);
+        assert!(!moved.contains(&(0, 1)) && !moved.contains(&(1, 0)));
+        assert!(moved.contains(&(1, 1)));
+    }
+}
@@ -0,0 +1,336 @@
+use vecx::{Matrix, Vec3};
+
+use crate::lighting::LightList;
+use crate::material::Material;
+use crate::mesh::{Mesh, Transform};
+use crate::model::Model;
+use crate::projection::Camera;
+
+/// Handle to a node of a `Scene`, only meaningful for the scene that returned it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct NodeId(usize);
+
+/// Element of the scene graph, placed by its transform relative to its parent. A node's
+/// mesh is placed by the mesh's own transform relative to the node
+pub struct SceneNode {
+    name: String,
+    pub transform: Transform,
+    mesh: Option<Mesh>,
+    parent: Option<NodeId>,
+    children: Vec<NodeId>,
+    visible: bool,
+}
+
+impl SceneNode {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn mesh(&self) -> Option<&Mesh> {
+        self.mesh.as_ref()
+    }
+
+    pub fn mesh_mut(&mut self) -> Option<&mut Mesh> {
+        self.mesh.as_mut()
+    }
+
+    pub fn set_mesh(&mut self, mesh: Option<Mesh>) {
+        self.mesh = mesh;
+    }
+
+    pub fn parent(&self) -> Option<NodeId> {
+        self.parent
+    }
+
+    pub fn children(&self) -> &[NodeId] {
+        &self.children
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.visible
+    }
+
+    /// Hidden nodes are not rendered, nor are their children
+    pub fn set_visible(&mut self, visible: bool) {
+        self.visible = visible;
+    }
+}
+
+/// Everything `EngineCore::render_scene` draws: a tree of named nodes holding meshes,
+/// named cameras and the lights shining on the meshes. Cameras and lights are in world
+/// space
+pub struct Scene {
+    nodes: Vec<SceneNode>,
+    cameras: Vec<(String, Camera)>,
+    active_camera: usize,
+    lights: LightList,
+    default_material: Material,
+}
+
+impl Scene {
+    pub fn new() -> Self {
+        let mut default_material = Material::new("default");
+        default_material.specular = Vec3(0.5, 0.5, 0.5);
+        default_material.shininess = 32.0;
+
+        Scene {
+            nodes: Vec::new(),
+            cameras: Vec::new(),
+            active_camera: 0,
+            lights: LightList::new(),
+            default_material,
+        }
+    }
+
+    /// Adds a node under `parent`, or at the root of the scene
+    pub fn add_node(&mut self, name: &str, parent: Option<NodeId>, mesh: Option<Mesh>) -> NodeId {
+        let id = NodeId(self.nodes.len());
+        self.nodes.push(SceneNode {
+            name: name.to_string(),
+            transform: Transform::default(),
+            mesh,
+            parent,
+            children: Vec::new(),
+            visible: true,
+        });
+
+        if let Some(parent) = parent {
+            self.nodes[parent.0].children.push(id);
+        }
+        id
+    }
+
+    /// Adds the mesh in a root node, or replaces the mesh of the node with the same name
+    pub fn add_mesh(&mut self, name: &str, mesh: Mesh) -> NodeId {
+        match self.find_node(name) {
+            Some(id) => {
+                self.nodes[id.0].mesh = Some(mesh);
+                id
+            }
+            None => self.add_node(name, None, Some(mesh)),
+        }
+    }
+
+    /// Adds a node without mesh under `parent` holding a child node per part of the model,
+    /// named after the part
+    pub fn add_model(&mut self, name: &str, parent: Option<NodeId>, model: Model) -> NodeId {
+        let id = self.add_node(name, parent, None);
+        for part in model.into_parts() {
+            self.add_node(&part.name, Some(id), Some(part.mesh));
+        }
+        id
+    }
+
+    /// Takes the mesh out of its node, the node itself stays so its children keep their
+    /// place
+    pub fn remove_mesh(&mut self, name: &str) -> Option<Mesh> {
+        let id = self.find_node(name)?;
+        self.nodes[id.0].mesh.take()
+    }
+
+    pub fn mesh(&self, name: &str) -> Option<&Mesh> {
+        self.node(self.find_node(name)?).mesh()
+    }
+
+    pub fn mesh_mut(&mut self, name: &str) -> Option<&mut Mesh> {
+        let id = self.find_node(name)?;
+        self.node_mut(id).mesh_mut()
+    }
+
+    /// Every mesh with its node's name, parents before their children
+    pub fn meshes(&self) -> impl Iterator<Item = (&str, &Mesh)> {
+        self.depth_first().into_iter().filter_map(|id| {
+            self.nodes[id.0]
+                .mesh()
+                .map(|mesh| (self.nodes[id.0].name(), mesh))
+        })
+    }
+
+    /// First node added with that name
+    pub fn find_node(&self, name: &str) -> Option<NodeId> {
+        self.nodes
+            .iter()
+            .position(|node| node.name == name)
+            .map(NodeId)
+    }
+
+    pub fn node(&self, id: NodeId) -> &SceneNode {
+        &self.nodes[id.0]
+    }
+
+    pub fn node_mut(&mut self, id: NodeId) -> &mut SceneNode {
+        &mut self.nodes[id.0]
+    }
+
+    /// Moves the node under another parent, or to the root. Returns false and changes
+    /// nothing when the new parent is the node itself or one of its descendants
+    pub fn set_parent(&mut self, id: NodeId, parent: Option<NodeId>) -> bool {
+        let mut ancestor = parent;
+        while let Some(ancestor_id) = ancestor {
+            if ancestor_id == id {
+                return false;
+            }
+            ancestor = self.nodes[ancestor_id.0].parent;
+        }
+
+        if let Some(old_parent) = self.nodes[id.0].parent {
+            self.nodes[old_parent.0]
+                .children
+                .retain(|child| *child != id);
+        }
+        if let Some(parent) = parent {
+            self.nodes[parent.0].children.push(id);
+        }
+        self.nodes[id.0].parent = parent;
+        true
+    }
+
+    /// Node to world matrix, the node's transform composed with all of its ancestors'
+    pub fn world_matrix(&self, id: NodeId) -> Matrix {
+        let node = &self.nodes[id.0];
+        match node.parent {
+            Some(parent) => self.world_matrix(parent) * node.transform.matrix(),
+            None => node.transform.matrix(),
+        }
+    }
+
+    /// Every visible mesh with its world and normal matrices, parents before their
+    /// children. Each node's matrices are only computed once
+    pub fn mesh_instances(&self) -> Vec<(&Mesh, Matrix, Matrix)> {
+        let mut instances = Vec::new();
+        let roots = self
+            .nodes
+            .iter()
+            .enumerate()
+            .filter(|(_, node)| node.parent.is_none());
+        for (index, _) in roots {
+            self.collect_instances(
+                NodeId(index),
+                &Matrix::id4(),
+                &Matrix::id4(),
+                &mut instances,
+            );
+        }
+        instances
+    }
+
+    fn collect_instances<'a>(
+        &'a self,
+        id: NodeId,
+        parent_matrix: &Matrix,
+        parent_normal_matrix: &Matrix,
+        instances: &mut Vec<(&'a Mesh, Matrix, Matrix)>,
+    ) {
+        let node = &self.nodes[id.0];
+        if !node.visible {
+            return;
+        }
+
+        let world_matrix = parent_matrix * &node.transform.matrix();
+        let normal_matrix = parent_normal_matrix * &node.transform.normal_matrix();
+
+        if let Some(mesh) = node.mesh() {
+            instances.push((
+                mesh,
+                &world_matrix * &mesh.transform.matrix(),
+                &normal_matrix * &mesh.transform.normal_matrix(),
+            ));
+        }
+
+        for child in node.children.iter() {
+            self.collect_instances(*child, &world_matrix, &normal_matrix, instances);
+        }
+    }
+
+    /// Node ids from the roots down, parents before their children
+    fn depth_first(&self) -> Vec<NodeId> {
+        let mut ids = Vec::with_capacity(self.nodes.len());
+        let mut stack: Vec<NodeId> = (0..self.nodes.len())
+            .rev()
+            .filter(|index| self.nodes[*index].parent.is_none())
+            .map(NodeId)
+            .collect();
+
+        while let Some(id) = stack.pop() {
+            ids.push(id);
+            stack.extend(self.nodes[id.0].children.iter().rev());
+        }
+        ids
+    }
+
+    /// Adds the camera, replacing any camera with the same name. The first camera added
+    /// becomes the active one
+    pub fn add_camera(&mut self, name: &str, camera: Camera) {
+        match self.cameras.iter_mut().find(|(n, _)| n == name) {
+            Some(entry) => entry.1 = camera,
+            None => self.cameras.push((name.to_string(), camera)),
+        }
+    }
+
+    pub fn camera(&self, name: &str) -> Option<&Camera> {
+        self.cameras
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, camera)| camera)
+    }
+
+    pub fn camera_mut(&mut self, name: &str) -> Option<&mut Camera> {
+        self.cameras
+            .iter_mut()
+            .find(|(n, _)| n == name)
+            .map(|(_, camera)| camera)
+    }
+
+    pub fn cameras(&self) -> impl Iterator<Item = (&str, &Camera)> {
+        self.cameras
+            .iter()
+            .map(|(name, camera)| (name.as_str(), camera))
+    }
+
+    /// Camera the scene is rendered from, None until a camera is added
+    pub fn active_camera(&self) -> Option<&Camera> {
+        self.cameras
+            .get(self.active_camera)
+            .map(|(_, camera)| camera)
+    }
+
+    pub fn active_camera_mut(&mut self) -> Option<&mut Camera> {
+        self.cameras
+            .get_mut(self.active_camera)
+            .map(|(_, camera)| camera)
+    }
+
+    /// Returns false and keeps the current camera when there is no camera by that name
+    pub fn set_active_camera(&mut self, name: &str) -> bool {
+        match self.cameras.iter().position(|(n, _)| n == name) {
+            Some(index) => {
+                self.active_camera = index;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn lights(&self) -> &LightList {
+        &self.lights
+    }
+
+    pub fn lights_mut(&mut self) -> &mut LightList {
+        &mut self.lights
+    }
+
+    /// Material lighting faces whose mesh has none, in the Phong render mode
+    pub fn default_material(&self) -> &Material {
+        &self.default_material
+    }
+
+    pub fn set_default_material(&mut self, material: Material) {
+        self.default_material = material;
+    }
+}
+
+impl Default for Scene {
+    fn default() -> Self {
+        Self::new()
+    }
+}
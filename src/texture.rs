@@ -0,0 +1,98 @@
+use std::error::Error;
+
+use vecx::{Vec2, VecX};
+
+/// Image sampled by textured triangles, pixels are stored as ARGB like the color buffer's
+pub struct Texture {
+    width: usize,
+    height: usize,
+    pixels: Vec<u32>,
+}
+
+impl Texture {
+    pub fn new(width: usize, height: usize, pixels: Vec<u32>) -> Self {
+        assert_eq!(pixels.len(), width * height, "texture size mismatch");
+        Texture {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    /// Loads any image format supported by the `image` crate
+    pub fn load(path: &str) -> Result<Self, Box<dyn Error>> {
+        let image = image::open(path)?.to_rgba8();
+        let (width, height) = image.dimensions();
+
+        let pixels = image
+            .pixels()
+            .map(|pixel| {
+                let [r, g, b, a] = pixel.0;
+                u32::from_be_bytes([a, r, g, b])
+            })
+            .collect();
+
+        Ok(Texture::new(width as usize, height as usize, pixels))
+    }
+
+    /// Texture made of `squares` x `squares` alternating squares, handy when no image is at hand
+    pub fn checkerboard(size: usize, squares: usize, color1: u32, color2: u32) -> Self {
+        let square_size = (size / squares.max(1)).max(1);
+        let pixels = (0..size * size)
+            .map(|i| {
+                let x = (i % size) / square_size;
+                let y = (i / size) / square_size;
+                if (x ^ y) & 1 == 0 {
+                    color1
+                } else {
+                    color2
+                }
+            })
+            .collect();
+
+        Texture::new(size, size, pixels)
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> u32 {
+        self.pixels[(self.width * y) + x]
+    }
+
+    /// Nearest neighbour lookup, coordinates outside [0, 1] wrap around and v points up
+    /// like in OBJ files
+    pub fn sample(&self, uv: Vec2) -> u32 {
+        if self.pixels.is_empty() {
+            return 0;
+        }
+
+        let u = uv.x() - uv.x().floor();
+        let v = uv.y() - uv.y().floor();
+
+        let x = ((u * self.width as f64) as usize).min(self.width - 1);
+        let y = (((1.0 - v) * self.height as f64) as usize).min(self.height - 1);
+
+        self.pixel(x, y)
+    }
+}
+
+/// Multiplies each channel of `color` by the matching channel of `tint`
+pub(crate) fn tint(color: u32, tint: u32) -> u32 {
+    let [a, r, g, b] = color.to_be_bytes();
+    let [ta, tr, tg, tb] = tint.to_be_bytes();
+
+    let multiply = |c: u8, t: u8| ((c as u32 * t as u32) / 255) as u8;
+
+    u32::from_be_bytes([
+        multiply(a, ta),
+        multiply(r, tr),
+        multiply(g, tg),
+        multiply(b, tb),
+    ])
+}